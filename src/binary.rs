@@ -0,0 +1,641 @@
+use std::{
+    fmt,
+    io::{self, BufRead, Read},
+};
+
+use crate::{
+    presents::{OrientationPolicy, Present, PresentPossibilities},
+    puzzle::Puzzle,
+    region::{Pin, RegionSpec, SpecError},
+    space::Space,
+};
+
+// Layout, version 1. Every number is an unsigned LEB128 varint except where noted.
+//   magic        b"AOCP"
+//   version      u8
+//   shape count
+//   per shape    u16 little endian bitmask, bit row*3+col set if occupied
+//                u8 policy: 0 fixed, 1 rotations, 2 dihedral
+//   region count
+//   per region   width, height, one count per shape,
+//                blocked count then x, y per cell,
+//                pinned count then present, orientation, x, y per pin,
+//                with x and y zigzag encoded since a pin can start at -1
+pub const MAGIC: &[u8; 4] = b"AOCP";
+pub const VERSION: u8 = 1;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BinaryError {
+    BadMagic,
+    UnsupportedVersion(u8),
+    Truncated,
+    BadPolicy(u8),
+    EmptyShape,
+    VarintOverflow,
+    BlockedOutside(usize, usize),
+    UnknownPresent(Pin),
+    PinOutside(Pin),
+    Read(io::ErrorKind),
+}
+
+pub fn encode(puzzle: &Puzzle) -> Vec<u8> {
+    let mut bytes: Vec<u8> = Vec::new();
+    bytes.extend_from_slice(MAGIC);
+    bytes.push(VERSION);
+
+    write_varint(&mut bytes, puzzle.shapes.len());
+    for shape in &puzzle.shapes {
+        bytes.extend_from_slice(&shape_mask(&shape.possibilities[0]).to_le_bytes());
+        bytes.push(match shape.policy {
+            OrientationPolicy::Fixed => 0,
+            OrientationPolicy::Rotations => 1,
+            OrientationPolicy::Dihedral => 2,
+        });
+    }
+
+    write_varint(&mut bytes, puzzle.regions.len());
+    for region in &puzzle.regions {
+        write_varint(&mut bytes, region.width);
+        write_varint(&mut bytes, region.height);
+        for &count in &region.demand {
+            write_varint(&mut bytes, count);
+        }
+        write_varint(&mut bytes, region.blocked.len());
+        for &(x, y) in &region.blocked {
+            write_varint(&mut bytes, x);
+            write_varint(&mut bytes, y);
+        }
+        write_varint(&mut bytes, region.pinned.len());
+        for pin in &region.pinned {
+            for value in [pin.present_idx, pin.poss_idx, zigzag(pin.x), zigzag(pin.y)] {
+                write_varint(&mut bytes, value);
+            }
+        }
+    }
+    bytes
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Puzzle, BinaryError> {
+    let reader = BinaryPuzzle::new(bytes)?;
+    let regions = reader
+        .regions()
+        .map(|region| region.and_then(|region| region.to_spec()))
+        .collect::<Result<Vec<RegionSpec>, BinaryError>>()?;
+    Ok(Puzzle {
+        shapes: reader.shapes,
+        regions,
+    })
+}
+
+pub fn text_to_binary(input: &str) -> Vec<u8> {
+    encode(&Puzzle::new(input))
+}
+
+pub fn binary_to_text(bytes: &[u8]) -> Result<String, BinaryError> {
+    Ok(decode(bytes)?.to_string())
+}
+
+// Reads the shapes up front and then walks the regions straight out of the
+// buffer, one at a time, without copying them
+#[derive(Debug)]
+pub struct BinaryPuzzle<'a> {
+    pub shapes: Vec<PresentPossibilities>,
+    region_count: usize,
+    region_bytes: &'a [u8],
+}
+
+impl<'a> BinaryPuzzle<'a> {
+    pub fn new(bytes: &'a [u8]) -> Result<Self, BinaryError> {
+        let mut cursor = Cursor { bytes };
+        let (shapes, region_count) = cursor.header()?;
+        Ok(Self {
+            shapes,
+            region_count,
+            region_bytes: cursor.bytes,
+        })
+    }
+
+    pub fn region_count(&self) -> usize {
+        self.region_count
+    }
+
+    pub fn regions(&self) -> Regions<'a> {
+        Regions {
+            cursor: Cursor {
+                bytes: self.region_bytes,
+            },
+            shape_count: self.shapes.len(),
+            remaining: self.region_count,
+        }
+    }
+}
+
+pub struct Regions<'a> {
+    cursor: Cursor<&'a [u8]>,
+    shape_count: usize,
+    remaining: usize,
+}
+
+impl<'a> Iterator for Regions<'a> {
+    type Item = Result<RegionRecord<'a>, BinaryError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let record = self.record();
+        if record.is_err() {
+            self.remaining = 0;
+        }
+        Some(record)
+    }
+}
+
+impl<'a> Regions<'a> {
+    fn record(&mut self) -> Result<RegionRecord<'a>, BinaryError> {
+        let width = self.cursor.varint()?;
+        let height = self.cursor.varint()?;
+        let demand = self.cursor.skip_varints(Some(self.shape_count))?;
+        let blocked_count = self.cursor.varint()?;
+        let blocked = self.cursor.skip_varints(blocked_count.checked_mul(2))?;
+        let pinned_count = self.cursor.varint()?;
+        let pinned = self.cursor.skip_varints(pinned_count.checked_mul(4))?;
+        Ok(RegionRecord {
+            width,
+            height,
+            demand,
+            blocked,
+            pinned,
+        })
+    }
+}
+
+// Reads the header like `BinaryPuzzle`, then decodes one region at a time
+// as it is read, so only the current region is ever held in memory
+pub struct BinaryStream<R> {
+    cursor: Cursor<R>,
+    pub shapes: Vec<PresentPossibilities>,
+    remaining: usize,
+}
+
+impl<R: BufRead> BinaryStream<R> {
+    pub fn new(reader: R) -> Result<Self, BinaryError> {
+        let mut cursor = Cursor { bytes: reader };
+        let (shapes, remaining) = cursor.header()?;
+        Ok(Self {
+            cursor,
+            shapes,
+            remaining,
+        })
+    }
+
+    fn region(&mut self) -> Result<RegionSpec, BinaryError> {
+        let width = self.cursor.varint()?;
+        let height = self.cursor.varint()?;
+        let mut demand: Vec<usize> = Vec::new();
+        for _ in 0..self.shapes.len() {
+            demand.push(self.cursor.varint()?);
+        }
+        let mut blocked: Vec<(usize, usize)> = Vec::new();
+        for _ in 0..self.cursor.varint()? {
+            blocked.push((self.cursor.varint()?, self.cursor.varint()?));
+        }
+        let mut pinned: Vec<Pin> = Vec::new();
+        for _ in 0..self.cursor.varint()? {
+            pinned.push(Pin {
+                present_idx: self.cursor.varint()?,
+                poss_idx: self.cursor.varint()?,
+                x: unzigzag(self.cursor.varint()?),
+                y: unzigzag(self.cursor.varint()?),
+            });
+        }
+        Ok(RegionSpec::try_from_parts(
+            width, height, demand, blocked, pinned,
+        )?)
+    }
+}
+
+impl<R: BufRead> Iterator for BinaryStream<R> {
+    type Item = Result<RegionSpec, BinaryError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let region = self.region();
+        if region.is_err() {
+            self.remaining = 0;
+        }
+        Some(region)
+    }
+}
+
+// A region still in its encoded form; the lists decode as they are read
+#[derive(Clone, Copy, Debug)]
+pub struct RegionRecord<'a> {
+    pub width: usize,
+    pub height: usize,
+    demand: &'a [u8],
+    blocked: &'a [u8],
+    pinned: &'a [u8],
+}
+
+impl<'a> RegionRecord<'a> {
+    pub fn demand(&self) -> impl Iterator<Item = usize> + 'a {
+        varints(self.demand)
+    }
+
+    pub fn blocked(&self) -> impl Iterator<Item = (usize, usize)> + 'a {
+        let mut values = varints(self.blocked);
+        std::iter::from_fn(move || Some((values.next()?, values.next()?)))
+    }
+
+    pub fn pinned(&self) -> impl Iterator<Item = Pin> + 'a {
+        let mut values = varints(self.pinned);
+        std::iter::from_fn(move || {
+            Some(Pin {
+                present_idx: values.next()?,
+                poss_idx: values.next()?,
+                x: unzigzag(values.next()?),
+                y: unzigzag(values.next()?),
+            })
+        })
+    }
+
+    // Same answer as `Tree::simple_check`, straight from the bytes
+    pub fn area_fits(&self, shapes: &[PresentPossibilities]) -> bool {
+        let needed: usize = self
+            .demand()
+            .zip(shapes)
+            .map(|(count, shape)| count * shape.get_size())
+            .sum();
+        let free = (self.width.saturating_mul(self.height)).saturating_sub(self.blocked().count());
+        needed <= free
+    }
+
+    // The lists are only checked against the region here, so a corrupt
+    // record can still be walked
+    pub fn to_spec(&self) -> Result<RegionSpec, BinaryError> {
+        Ok(RegionSpec::try_from_parts(
+            self.width,
+            self.height,
+            self.demand().collect(),
+            self.blocked().collect(),
+            self.pinned().collect(),
+        )?)
+    }
+}
+
+// Reads from a slice of the file or straight from a reader; the slice is
+// what `Read` is implemented on, so both go through the same decoding
+struct Cursor<R> {
+    bytes: R,
+}
+
+impl<R: Read> Cursor<R> {
+    fn byte(&mut self) -> Result<u8, BinaryError> {
+        let mut byte = [0];
+        match self.bytes.read_exact(&mut byte) {
+            Ok(()) => Ok(byte[0]),
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Err(BinaryError::Truncated),
+            Err(err) => Err(BinaryError::Read(err.kind())),
+        }
+    }
+
+    fn varint(&mut self) -> Result<usize, BinaryError> {
+        let mut value: usize = 0;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte()?;
+            let bits = (byte & 0x7f) as usize;
+            // Anything that would be shifted past the top is a corrupt file
+            if shift >= usize::BITS || (bits << shift) >> shift != bits {
+                return Err(BinaryError::VarintOverflow);
+            }
+            value |= bits << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+            shift += 7;
+        }
+    }
+
+    // Magic, version, shapes and the region count
+    fn header(&mut self) -> Result<(Vec<PresentPossibilities>, usize), BinaryError> {
+        let mut magic = [0; MAGIC.len()];
+        for byte in &mut magic {
+            *byte = self.byte().map_err(|_| BinaryError::BadMagic)?;
+        }
+        if &magic != MAGIC {
+            return Err(BinaryError::BadMagic);
+        }
+        let version = self.byte()?;
+        if version != VERSION {
+            return Err(BinaryError::UnsupportedVersion(version));
+        }
+
+        // The count comes from the file, so nothing is reserved up front
+        let shape_count = self.varint()?;
+        let mut shapes: Vec<PresentPossibilities> = Vec::new();
+        for _ in 0..shape_count {
+            let mask = u16::from_le_bytes([self.byte()?, self.byte()?]);
+            if mask & 0x1ff == 0 {
+                return Err(BinaryError::EmptyShape);
+            }
+            let policy = match self.byte()? {
+                0 => OrientationPolicy::Fixed,
+                1 => OrientationPolicy::Rotations,
+                2 => OrientationPolicy::Dihedral,
+                other => return Err(BinaryError::BadPolicy(other)),
+            };
+            shapes.push(PresentPossibilities::with_policy(&mask_shape(mask), policy));
+        }
+
+        let region_count = self.varint()?;
+        Ok((shapes, region_count))
+    }
+}
+
+impl<'a> Cursor<&'a [u8]> {
+    // Checks `count` varints are there and hands back the bytes they span.
+    // A count too big to even work out can't be there either.
+    fn skip_varints(&mut self, count: Option<usize>) -> Result<&'a [u8], BinaryError> {
+        let count = count.ok_or(BinaryError::Truncated)?;
+        let start = self.bytes;
+        for _ in 0..count {
+            self.varint()?;
+        }
+        Ok(&start[..start.len() - self.bytes.len()])
+    }
+}
+
+fn varints(bytes: &[u8]) -> impl Iterator<Item = usize> + '_ {
+    let mut cursor = Cursor { bytes };
+    std::iter::from_fn(move || cursor.varint().ok())
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: usize) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+// 0, -1, 1, -2, ... as 0, 1, 2, 3, ..., so small negatives stay small
+fn zigzag(value: isize) -> usize {
+    ((value << 1) ^ (value >> (isize::BITS - 1))) as usize
+}
+
+fn unzigzag(value: usize) -> isize {
+    (value >> 1) as isize ^ -((value & 1) as isize)
+}
+
+fn shape_mask(present: &Present) -> u16 {
+    let mut mask = 0;
+    for (row_idx, row) in present.spaces.iter().enumerate() {
+        for (col_idx, space) in row.iter().enumerate() {
+            if *space == Space::Occupied {
+                mask |= 1 << (row_idx * 3 + col_idx);
+            }
+        }
+    }
+    mask
+}
+
+fn mask_shape(mask: u16) -> String {
+    let mut shape = String::with_capacity(11);
+    for row in 0..3 {
+        for col in 0..3 {
+            shape.push(if mask & (1 << (row * 3 + col)) != 0 {
+                '#'
+            } else {
+                '.'
+            });
+        }
+        shape.push('\n');
+    }
+    shape
+}
+
+mod formatting {
+    use super::*;
+
+    impl fmt::Display for BinaryError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Self::BadMagic => write!(f, "Not a binary puzzle file"),
+                Self::UnsupportedVersion(version) => {
+                    write!(f, "Unsupported binary puzzle version {version}")
+                }
+                Self::Truncated => write!(f, "Binary puzzle file ends early"),
+                Self::BadPolicy(policy) => write!(f, "Unknown orientation policy byte {policy}"),
+                Self::EmptyShape => write!(f, "Binary puzzle shape has no occupied cells"),
+                Self::VarintOverflow => write!(f, "Binary puzzle number is too big"),
+                Self::BlockedOutside(x, y) => {
+                    write!(f, "Blocked cell {x},{y} is outside the region")
+                }
+                Self::UnknownPresent(pin) => write!(f, "Pin {pin} is of an unknown present"),
+                Self::PinOutside(pin) => write!(f, "Pin {pin} is outside the region"),
+                Self::Read(kind) => write!(f, "Could not read binary puzzle: {kind}"),
+            }
+        }
+    }
+}
+
+impl std::error::Error for BinaryError {}
+
+impl From<SpecError> for BinaryError {
+    fn from(err: SpecError) -> Self {
+        match err {
+            SpecError::BlockedOutside(x, y) => Self::BlockedOutside(x, y),
+            SpecError::UnknownPresent(pin) => Self::UnknownPresent(pin),
+            SpecError::PinOutside(pin) => Self::PinOutside(pin),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        file_parser::FileParser,
+        generator::{GeneratorConfig, generate},
+        tree::Tree,
+    };
+
+    #[test]
+    fn varint_round_trip() {
+        for value in [0, 1, 127, 128, 300, 16_384, usize::MAX] {
+            let mut bytes: Vec<u8> = Vec::new();
+            write_varint(&mut bytes, value);
+            assert_eq!(Cursor { bytes: &bytes[..] }.varint(), Ok(value));
+        }
+        for value in [0, -1, 1, -2, isize::MIN, isize::MAX] {
+            assert_eq!(unzigzag(zigzag(value)), value);
+        }
+        assert_eq!(zigzag(-1), 1);
+
+        let mut too_big = vec![0xff; 9];
+        too_big.push(0x02);
+        assert_eq!(
+            Cursor {
+                bytes: &too_big[..]
+            }
+            .varint(),
+            Err(BinaryError::VarintOverflow)
+        );
+        let mut endless = b"AOCP\x01".to_vec();
+        endless.extend([0xff; 12]);
+        assert_eq!(decode(&endless).unwrap_err(), BinaryError::VarintOverflow);
+    }
+
+    // One shape, then one region made of `values`
+    fn one_region(values: &[usize]) -> Vec<u8> {
+        let mut bytes = b"AOCP\x01".to_vec();
+        write_varint(&mut bytes, 1);
+        bytes.extend([0xff, 0x01, 0]);
+        write_varint(&mut bytes, 1);
+        for &value in values {
+            write_varint(&mut bytes, value);
+        }
+        bytes
+    }
+
+    #[test]
+    fn corrupt_input() {
+        let mut huge = b"AOCP\x01".to_vec();
+        write_varint(&mut huge, 1 << 56);
+        assert_eq!(decode(&huge).unwrap_err(), BinaryError::Truncated);
+        let mut empty = one_region(&[4, 4, 1, 0, 0]);
+        empty[6] = 0;
+        empty[7] = 0;
+        assert_eq!(decode(&empty).unwrap_err(), BinaryError::EmptyShape);
+
+        let pin = |present_idx, x, y| Pin {
+            present_idx,
+            poss_idx: 0,
+            x,
+            y,
+        };
+        for (values, expected) in [
+            (vec![4, 4, 1, 1, 3, 9, 0], BinaryError::BlockedOutside(3, 9)),
+            (
+                vec![4, 4, 1, 0, 1, 1, 0, 0, 0],
+                BinaryError::UnknownPresent(pin(1, 0, 0)),
+            ),
+            (
+                vec![4, 4, 1, 0, 1, 0, 0, zigzag(4), 0],
+                BinaryError::PinOutside(pin(0, 4, 0)),
+            ),
+            (
+                vec![4, 4, 1, 0, 1, 0, 0, zigzag(-2), 0],
+                BinaryError::PinOutside(pin(0, -2, 0)),
+            ),
+            (vec![4, 4, 1, usize::MAX, 0], BinaryError::Truncated),
+            (vec![4, 4, 1, 0, usize::MAX / 2], BinaryError::Truncated),
+        ] {
+            let bytes = one_region(&values);
+            assert_eq!(decode(&bytes).unwrap_err(), expected, "{values:?}");
+            let mut stream = BinaryStream::new(&bytes[..]).unwrap();
+            assert_eq!(stream.next(), Some(Err(expected)), "{values:?}");
+            assert_eq!(stream.next(), None);
+        }
+        assert!(decode(&one_region(&[4, 4, 1, 0, 0])).is_ok());
+    }
+
+    #[test]
+    fn example_round_trip() {
+        let my_file = FileParser::new("data/example.txt");
+        let puzzle = Puzzle::new(my_file.get_str());
+
+        let bytes = encode(&puzzle);
+
+        assert_eq!(&bytes[..4], MAGIC);
+        assert_eq!(decode(&bytes).unwrap(), puzzle);
+        assert_eq!(
+            binary_to_text(&text_to_binary(my_file.get_str())).unwrap(),
+            puzzle.to_string()
+        );
+    }
+
+    #[test]
+    fn extended_round_trip() {
+        let puzzle = Puzzle::new(
+            "0: fixed\n###\n#..\n###\n\n1:\n##.\n...\n...\n\n\
+             300x2: 1 200 | blocked 0,0 299,1 | pinned 0:0@5,0\n4x4: 0 1",
+        );
+
+        assert_eq!(decode(&encode(&puzzle)).unwrap(), puzzle);
+    }
+
+    #[test]
+    fn compact() {
+        let shapes = vec![
+            PresentPossibilities::new("###\n##.\n##."),
+            PresentPossibilities::new("###\n.#.\n###"),
+        ];
+        let config = GeneratorConfig {
+            seed: 2,
+            regions: 50,
+            widths: 10..=40,
+            heights: 10..=40,
+            unsolvable_percent: 50,
+        };
+        let text = generate(&shapes, &config).text;
+
+        let bytes = text_to_binary(&text);
+
+        assert!(bytes.len() * 2 < text.len());
+        assert_eq!(binary_to_text(&bytes).unwrap(), text);
+    }
+
+    #[test]
+    fn records_feed_solvers() {
+        let my_file = FileParser::new("data/example.txt");
+        let puzzle = Puzzle::new(my_file.get_str());
+        let bytes = encode(&puzzle);
+
+        let reader = BinaryPuzzle::new(&bytes).unwrap();
+
+        assert_eq!(reader.region_count(), 3);
+        for (record, spec) in reader.regions().zip(&puzzle.regions) {
+            let record = record.unwrap();
+            let tree = Tree::from_spec(spec, &reader.shapes);
+            assert_eq!(record.area_fits(&reader.shapes), tree.simple_check());
+            assert_eq!(record.demand().collect::<Vec<usize>>(), spec.demand);
+        }
+
+        let stream = BinaryStream::new(&bytes[..]).unwrap();
+        assert_eq!(stream.shapes, puzzle.shapes);
+        let regions: Vec<RegionSpec> = stream.map(Result::unwrap).collect();
+        assert_eq!(regions, puzzle.regions);
+    }
+
+    #[test]
+    fn errors() {
+        let bytes = text_to_binary(FileParser::new("data/example.txt").get_str());
+
+        assert_eq!(
+            BinaryPuzzle::new(b"text").unwrap_err(),
+            BinaryError::BadMagic
+        );
+
+        let mut newer = bytes.clone();
+        newer[4] = 9;
+        assert_eq!(
+            BinaryPuzzle::new(&newer).unwrap_err(),
+            BinaryError::UnsupportedVersion(9)
+        );
+
+        let truncated = &bytes[..bytes.len() - 2];
+        assert_eq!(decode(truncated).unwrap_err(), BinaryError::Truncated);
+        assert!(BinaryError::Truncated.to_string().contains("ends early"));
+    }
+}
@@ -0,0 +1,316 @@
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    io::{self, ErrorKind},
+    sync::Mutex,
+};
+
+use crate::{presents::ShapeSet, region::RegionSpec, report::Verdict};
+
+const HEADER: &str = "# verdict cache v1";
+
+// Verdicts remembered across regions, and across runs once saved. A plain
+// rectangle that fits its demand also fits any smaller demand in any larger
+// rectangle, and one that doesn't fit rules out larger demands in smaller
+// rectangles, so one verdict answers many regions. Verdicts are kept per
+// set of shapes and per solver, since a heuristic solver's verdicts only
+// say what that solver would answer. Regions with blocked cells or pins
+// aren't cached.
+#[derive(Debug, Default)]
+pub struct VerdictCache {
+    buckets: Mutex<HashMap<(u64, String), Bucket>>,
+}
+
+// Only the entries no other entry implies
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct Bucket {
+    fits: Vec<Entry>,
+    fails: Vec<Entry>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Entry {
+    width: usize,
+    height: usize,
+    demand: Vec<usize>,
+}
+
+impl VerdictCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // A missing file is an empty cache
+    pub fn load(path: &str) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(Self::new()),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let cache = Self::new();
+        {
+            let mut buckets = cache.buckets.lock().unwrap();
+            for line in text.lines() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let (key, fits, entry) = parse_line(line).ok_or_else(|| {
+                    io::Error::new(
+                        ErrorKind::InvalidData,
+                        format!("Bad verdict cache line {line:?}"),
+                    )
+                })?;
+                buckets.entry(key).or_default().insert(fits, entry);
+            }
+        }
+        Ok(cache)
+    }
+
+    pub fn lookup(&self, shapes: &ShapeSet, solver: &str, spec: &RegionSpec) -> Option<Verdict> {
+        let (key, entry) = key(shapes, solver, spec)?;
+        let buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.get(&key)?;
+        if bucket.fits.iter().any(|known| known.covers(&entry)) {
+            Some(Verdict::Fits)
+        } else if bucket.fails.iter().any(|known| entry.covers(known)) {
+            Some(Verdict::DoesNotFit)
+        } else {
+            None
+        }
+    }
+
+    // Timeouts settle nothing and aren't kept
+    pub fn record(&self, shapes: &ShapeSet, solver: &str, spec: &RegionSpec, verdict: Verdict) {
+        let Some((key, entry)) = key(shapes, solver, spec) else {
+            return;
+        };
+        if !verdict.is_decided() {
+            return;
+        }
+        let mut buckets = self.buckets.lock().unwrap();
+        buckets
+            .entry(key)
+            .or_default()
+            .insert(verdict == Verdict::Fits, entry);
+    }
+
+    pub fn len(&self) -> usize {
+        let buckets = self.buckets.lock().unwrap();
+        buckets
+            .values()
+            .map(|bucket| bucket.fits.len() + bucket.fails.len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Bucket {
+    fn insert(&mut self, fits: bool, entry: Entry) {
+        if fits {
+            if self.fits.iter().any(|known| known.covers(&entry)) {
+                return;
+            }
+            self.fits.retain(|known| !entry.covers(known));
+            self.fits.push(entry);
+        } else {
+            if self.fails.iter().any(|known| entry.covers(known)) {
+                return;
+            }
+            self.fails.retain(|known| !known.covers(&entry));
+            self.fails.push(entry);
+        }
+    }
+}
+
+impl Entry {
+    // Whether `other` is no larger a problem: at least as much room, and no
+    // more of any present
+    fn covers(&self, other: &Entry) -> bool {
+        other.width >= self.width
+            && other.height >= self.height
+            && other.demand.len() == self.demand.len()
+            && other
+                .demand
+                .iter()
+                .zip(&self.demand)
+                .all(|(theirs, ours)| theirs <= ours)
+    }
+}
+
+// When every shape can be mirrored across the diagonal, a region and its
+// transpose have the same answer, so the shorter side always comes first
+fn key(shapes: &ShapeSet, solver: &str, spec: &RegionSpec) -> Option<((u64, String), Entry)> {
+    if !spec.blocked.is_empty() || !spec.pinned.is_empty() {
+        return None;
+    }
+    let (mut width, mut height) = (spec.width, spec.height);
+    if width > height && shapes.iter().all(|shape| shape.is_transpose_closed()) {
+        std::mem::swap(&mut width, &mut height);
+    }
+    Some((
+        (shapes.fingerprint(), solver.to_string()),
+        Entry {
+            width,
+            height,
+            demand: spec.demand.clone(),
+        },
+    ))
+}
+
+// "<shapes> <solver> <W>x<H> fits|fails <demand..>"
+fn parse_line(line: &str) -> Option<((u64, String), bool, Entry)> {
+    let mut words = line.split_whitespace();
+    let fingerprint = u64::from_str_radix(words.next()?, 16).ok()?;
+    let solver = words.next()?.to_string();
+    let (width, height) = words.next()?.split_once('x')?;
+    let fits = match words.next()? {
+        "fits" => true,
+        "fails" => false,
+        _ => return None,
+    };
+    let demand = words
+        .map(|word| word.parse().ok())
+        .collect::<Option<Vec<usize>>>()?;
+    Some((
+        (fingerprint, solver),
+        fits,
+        Entry {
+            width: width.parse().ok()?,
+            height: height.parse().ok()?,
+            demand,
+        },
+    ))
+}
+
+mod formatting {
+    use super::*;
+
+    // Sorted, so saving the same cache twice writes the same file
+    impl fmt::Display for VerdictCache {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            writeln!(f, "{HEADER}")?;
+            let buckets = self.buckets.lock().unwrap();
+            let mut keys: Vec<&(u64, String)> = buckets.keys().collect();
+            keys.sort();
+            for key in keys {
+                let bucket = &buckets[key];
+                for (word, entries) in [("fits", &bucket.fits), ("fails", &bucket.fails)] {
+                    let mut entries: Vec<&Entry> = entries.iter().collect();
+                    entries.sort_by_key(|entry| (entry.width, entry.height, &entry.demand));
+                    for entry in entries {
+                        write!(
+                            f,
+                            "{:016x} {} {}x{} {word}",
+                            key.0, key.1, entry.width, entry.height
+                        )?;
+                        for count in &entry.demand {
+                            write!(f, " {count}")?;
+                        }
+                        writeln!(f)?;
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::presents::PresentPossibilities;
+
+    fn shapes() -> ShapeSet {
+        ShapeSet::new(vec![
+            PresentPossibilities::new("###\n#..\n###"),
+            PresentPossibilities::new("###\n###\n###"),
+        ])
+    }
+
+    #[test]
+    fn monotone() {
+        let shapes = shapes();
+        let cache = VerdictCache::new();
+        cache.record(&shapes, "pack", &RegionSpec::new("6x4: 1 1"), Verdict::Fits);
+        cache.record(
+            &shapes,
+            "pack",
+            &RegionSpec::new("3x3: 1 1"),
+            Verdict::DoesNotFit,
+        );
+
+        let lookup = |region: &str| cache.lookup(&shapes, "pack", &RegionSpec::new(region));
+        assert_eq!(lookup("6x4: 1 1"), Some(Verdict::Fits));
+        assert_eq!(lookup("7x5: 0 1"), Some(Verdict::Fits));
+        assert_eq!(lookup("4x6: 1 0"), Some(Verdict::Fits));
+        assert_eq!(lookup("3x3: 2 1"), Some(Verdict::DoesNotFit));
+        assert_eq!(lookup("3x2: 1 1"), Some(Verdict::DoesNotFit));
+        assert_eq!(lookup("5x4: 1 1"), None);
+        assert_eq!(lookup("6x4: 2 1"), None);
+
+        assert_eq!(
+            cache.lookup(&shapes, "area", &RegionSpec::new("6x4: 1 1")),
+            None
+        );
+        assert_eq!(
+            cache.lookup(&shapes, "pack", &RegionSpec::new("6x4: 1 1 | blocked 0,0")),
+            None
+        );
+    }
+
+    #[test]
+    fn keeps_the_strongest() {
+        let shapes = shapes();
+        let cache = VerdictCache::new();
+        cache.record(&shapes, "pack", &RegionSpec::new("8x8: 1 1"), Verdict::Fits);
+        cache.record(&shapes, "pack", &RegionSpec::new("9x9: 1 0"), Verdict::Fits);
+        cache.record(&shapes, "pack", &RegionSpec::new("6x6: 1 1"), Verdict::Fits);
+        cache.record(
+            &shapes,
+            "pack",
+            &RegionSpec::new("5x5: 9 9"),
+            Verdict::TimedOut,
+        );
+
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn round_trip() {
+        let shapes = shapes();
+        let cache = VerdictCache::new();
+        cache.record(&shapes, "pack", &RegionSpec::new("6x4: 1 1"), Verdict::Fits);
+        cache.record(
+            &shapes,
+            "race:backtrack,pack",
+            &RegionSpec::new("3x3: 1 1"),
+            Verdict::DoesNotFit,
+        );
+
+        let text = cache.to_string();
+        let loaded = VerdictCache::parse(&text).unwrap();
+
+        assert_eq!(loaded.to_string(), text);
+        assert_eq!(
+            loaded.lookup(&shapes, "race:backtrack,pack", &RegionSpec::new("3x3: 1 2")),
+            Some(Verdict::DoesNotFit)
+        );
+        assert!(VerdictCache::parse("0 pack 3x3 maybe 1").is_err());
+        assert!(
+            VerdictCache::load("data/no-such-cache.txt")
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
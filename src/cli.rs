@@ -0,0 +1,449 @@
+use std::{fmt, io, time::Duration};
+
+use crate::{
+    binary::BinaryError,
+    parse_error::ParseError,
+    solver::{Registry, SolveConfig},
+    tree::PinError,
+};
+
+mod commands;
+
+pub const USAGE: &str = "\
+Usage: aoc_2025_xx <command> [options]
+
+Commands:
+  solve <file|->            Decide every region, streaming verdicts as they come
+  check <file>              Parse and validate only
+  render <file> --region N  Draw region N (from 0) with a packing if the solver
+                            finds one
+  generate                  Write a random puzzle with known answers
+  stats <file>              Describe the shapes and regions
+  convert <in> <out>        Text to binary, or binary back to text
+
+Options:
+  --format text|json|csv    Output for solve, and text or json for stats
+                            (json needs the serde feature)
+  --solver NAME[,NAME..]    area compares areas, backtrack searches placements,
+                            pack finds a packing to show (default area, or
+                            pack for render).
+                            Several names try each in turn; race:NAME,NAME..
+                            runs them side by side and takes the first answer
+  --threads N               Regions solved at once (default 1)
+  --timeout SECONDS         Give up on a region, or on render, after this long
+  --cache <path>            Reuse verdicts saved there, and save the new ones
+  --checkpoint <dir>        Keep each backtrack search's progress there, and
+                            carry on from it when run again
+  --checkpoint-every SECONDS  How often to write it (default 60)
+  --progress SECONDS        Redraw a line on stderr this often with the nodes,
+                            depth, speed and prunes of the searches under way
+
+Generate options:
+  --seed N  --regions N  --width LO..HI  --height LO..HI  --unsolvable PERCENT
+  --shapes <file>           Take the shapes from a puzzle (default free pentominoes)
+  --out <path>              Write the puzzle and <path>.labels instead of printing
+";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    Help,
+    Solve { input: String },
+    Check { input: String },
+    Render { input: String, region: usize },
+    Generate(GenerateOptions),
+    Stats { input: String },
+    Convert { input: String, output: String },
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Text,
+    Json,
+    Csv,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    pub format: Format,
+    pub solve: SolveConfig,
+    // Verdict cache file for solve
+    pub cache: Option<String>,
+    // How often solve redraws its progress line, if at all
+    pub progress: Option<Duration>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GenerateOptions {
+    pub seed: u64,
+    pub regions: usize,
+    pub widths: (usize, usize),
+    pub heights: (usize, usize),
+    pub unsolvable_percent: usize,
+    pub shapes: Option<String>,
+    pub output: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Cli {
+    pub command: Command,
+    pub options: Options,
+}
+
+#[derive(Debug)]
+pub enum CliError {
+    Usage(String),
+    Parse(ParseError),
+    Pin(usize, PinError),
+    Binary(BinaryError),
+    Io(io::Error),
+}
+
+impl Default for GenerateOptions {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            regions: 10,
+            widths: (4, 12),
+            heights: (4, 12),
+            unsolvable_percent: 50,
+            shapes: None,
+            output: None,
+        }
+    }
+}
+
+impl Cli {
+    // Options can go anywhere after the command, as "--name value" or "--name=value"
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, CliError> {
+        let mut options = Options::default();
+        let mut generate = GenerateOptions::default();
+        let mut region: Option<usize> = None;
+        let mut solver: Option<String> = None;
+        let mut positional: Vec<String> = Vec::new();
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if arg == "-h" || arg == "--help" {
+                return Ok(Self {
+                    command: Command::Help,
+                    options,
+                });
+            }
+            let Some(name) = arg.strip_prefix("--") else {
+                positional.push(arg);
+                continue;
+            };
+
+            let (name, value) = match name.split_once('=') {
+                Some((name, value)) => (name.to_string(), value.to_string()),
+                None => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| usage(format!("--{name} needs a value")))?;
+                    (name.to_string(), value)
+                }
+            };
+            match name.as_str() {
+                "format" => {
+                    options.format = match value.as_str() {
+                        "text" => Format::Text,
+                        "json" if cfg!(feature = "serde") => Format::Json,
+                        "json" => return Err(usage(JSON_NEEDS_SERDE.to_string())),
+                        "csv" => Format::Csv,
+                        _ => return Err(usage(format!("Unknown format {value:?}"))),
+                    }
+                }
+                "solver" => solver = Some(value),
+                "threads" => {
+                    options.solve.threads = number(&name, &value)?;
+                    if options.solve.threads == 0 {
+                        return Err(usage("--threads must be at least 1".to_string()));
+                    }
+                }
+                "timeout" => options.solve.timeout = Some(seconds(&name, &value)?),
+                "cache" => options.cache = Some(value),
+                "progress" => options.progress = Some(seconds(&name, &value)?),
+                "checkpoint" => options.solve.checkpoint_dir = Some(value.into()),
+                "checkpoint-every" => options.solve.checkpoint_every = seconds(&name, &value)?,
+                "region" => region = Some(number(&name, &value)?),
+                "seed" => generate.seed = number(&name, &value)?,
+                "regions" => generate.regions = number(&name, &value)?,
+                "width" => generate.widths = range(&name, &value)?,
+                "height" => generate.heights = range(&name, &value)?,
+                "unsolvable" => {
+                    generate.unsolvable_percent = number(&name, &value)?;
+                    if generate.unsolvable_percent > 100 {
+                        return Err(usage("--unsolvable is a percentage".to_string()));
+                    }
+                }
+                "shapes" => generate.shapes = Some(value),
+                "out" => generate.output = Some(value),
+                _ => return Err(usage(format!("Unknown option --{name}"))),
+            }
+        }
+
+        let mut positional = positional.into_iter();
+        let command = positional.next().unwrap_or_else(|| "help".to_string());
+        let mut input = |what: &str| {
+            positional
+                .next()
+                .ok_or_else(|| usage(format!("{command} needs {what}")))
+        };
+        let command = match command.as_str() {
+            "help" => Command::Help,
+            "solve" => Command::Solve {
+                input: input("a file, or - for stdin")?,
+            },
+            "check" => Command::Check {
+                input: input("a file")?,
+            },
+            "render" => Command::Render {
+                input: input("a file")?,
+                region: region.ok_or_else(|| usage("render needs --region N".to_string()))?,
+            },
+            "generate" => Command::Generate(generate),
+            "stats" => Command::Stats {
+                input: input("a file")?,
+            },
+            "convert" => Command::Convert {
+                input: input("an input file")?,
+                output: input("an output file")?,
+            },
+            _ => return Err(usage(format!("Unknown command {command:?}"))),
+        };
+        if let Some(extra) = positional.next() {
+            return Err(usage(format!("Unexpected argument {extra:?}")));
+        }
+
+        // The area check has no packing to draw
+        let solver = match (&solver, &command) {
+            (Some(solver), _) => Some(solver.as_str()),
+            (None, Command::Render { .. }) => Some("pack"),
+            (None, _) => None,
+        };
+        if let Some(solver) = solver {
+            options.solve.solver = Registry::default()
+                .build(solver)
+                .map_err(|err| usage(err.to_string()))?;
+        }
+
+        Ok(Self { command, options })
+    }
+
+    pub fn run<W: io::Write>(&self, output: &mut W) -> Result<(), CliError> {
+        match &self.command {
+            Command::Help => write!(output, "{USAGE}")?,
+            Command::Solve { input } => commands::solve(input, &self.options, output)?,
+            Command::Check { input } => commands::check(input, output)?,
+            Command::Render { input, region } => {
+                commands::render(input, *region, &self.options, output)?
+            }
+            Command::Generate(generate) => commands::generate(generate, output)?,
+            Command::Stats { input } => commands::stats(input, &self.options, output)?,
+            Command::Convert {
+                input,
+                output: path,
+            } => commands::convert(input, path, output)?,
+        }
+        Ok(())
+    }
+}
+
+impl CliError {
+    // 2 for a bad command line, 1 for anything wrong with the input
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::Usage(_) => 2,
+            _ => 1,
+        }
+    }
+}
+
+impl From<io::Error> for CliError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<ParseError> for CliError {
+    fn from(err: ParseError) -> Self {
+        Self::Parse(err)
+    }
+}
+
+impl From<BinaryError> for CliError {
+    fn from(err: BinaryError) -> Self {
+        Self::Binary(err)
+    }
+}
+
+const JSON_NEEDS_SERDE: &str = "--format json needs the serde feature, build with --features serde";
+
+fn usage(message: String) -> CliError {
+    CliError::Usage(message)
+}
+
+fn number<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, CliError> {
+    value
+        .parse()
+        .map_err(|_| usage(format!("--{name} {value:?} isn't a number")))
+}
+
+fn seconds(name: &str, value: &str) -> Result<Duration, CliError> {
+    value
+        .parse()
+        .ok()
+        .filter(|seconds: &f64| seconds.is_finite() && *seconds >= 0.0)
+        .map(Duration::from_secs_f64)
+        .ok_or_else(|| usage(format!("--{name} {value:?} isn't seconds")))
+}
+
+// "LO..HI", or a single number for both ends
+fn range(name: &str, value: &str) -> Result<(usize, usize), CliError> {
+    let (low, high) = value.split_once("..").unwrap_or((value, value));
+    let (low, high) = (number(name, low)?, number(name, high)?);
+    if low == 0 || low > high {
+        return Err(usage(format!("--{name} {value:?} isn't a range")));
+    }
+    Ok((low, high))
+}
+
+mod formatting {
+    use super::*;
+
+    impl fmt::Display for CliError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Self::Usage(message) => write!(f, "{message}\n\n{USAGE}"),
+                Self::Parse(err) => write!(f, "{err}"),
+                Self::Pin(index, err) => write!(f, "Region {index}: {err}"),
+                Self::Binary(err) => write!(f, "{err}"),
+                Self::Io(err) => write!(f, "{err}"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Cli, CliError> {
+        Cli::parse(args.split_whitespace().map(str::to_string))
+    }
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!(parse("").unwrap().command, Command::Help);
+        assert_eq!(
+            parse("solve -").unwrap().command,
+            Command::Solve {
+                input: "-".to_string()
+            }
+        );
+        assert_eq!(
+            parse("render --region 2 data/example.txt").unwrap().command,
+            Command::Render {
+                input: "data/example.txt".to_string(),
+                region: 2
+            }
+        );
+
+        let Command::Generate(generate) = parse("generate --seed 4 --width 3..9 --height=5")
+            .unwrap()
+            .command
+        else {
+            panic!("Expected generate");
+        };
+        assert_eq!(generate.seed, 4);
+        assert_eq!(generate.widths, (3, 9));
+        assert_eq!(generate.heights, (5, 5));
+    }
+
+    #[test]
+    fn parse_options() {
+        let cli = parse("solve in.txt --format csv --solver=backtrack --threads 4 --timeout 0.5")
+            .unwrap();
+
+        assert_eq!(cli.options.format, Format::Csv);
+        assert_eq!(cli.options.solve.solver.name(), "backtrack");
+        assert_eq!(cli.options.solve.threads, 4);
+        assert_eq!(cli.options.solve.timeout, Some(Duration::from_millis(500)));
+        assert_eq!(cli.options.cache, None);
+        assert_eq!(cli.options.solve.checkpoint_dir, None);
+        assert_eq!(cli.options.progress, None);
+        assert_eq!(
+            parse("solve in.txt --progress 0.25")
+                .unwrap()
+                .options
+                .progress,
+            Some(Duration::from_millis(250))
+        );
+        let checkpointed = parse("solve in.txt --checkpoint-every 5 --checkpoint runs/1")
+            .unwrap()
+            .options
+            .solve;
+        assert_eq!(checkpointed.checkpoint_dir, Some("runs/1".into()));
+        assert_eq!(checkpointed.checkpoint_every, Duration::from_secs(5));
+        assert_eq!(
+            parse("solve in.txt --cache verdicts.txt")
+                .unwrap()
+                .options
+                .cache,
+            Some("verdicts.txt".to_string())
+        );
+        if cfg!(feature = "serde") {
+            assert_eq!(
+                parse("solve in.txt --format json").unwrap().options.format,
+                Format::Json
+            );
+        } else {
+            assert!(parse("solve in.txt --format json").is_err());
+        }
+        assert_eq!(
+            parse("solve in.txt").unwrap().options.solve.solver.name(),
+            "area"
+        );
+        assert_eq!(
+            parse("render in.txt --region 0")
+                .unwrap()
+                .options
+                .solve
+                .solver
+                .name(),
+            "pack"
+        );
+        assert_eq!(
+            parse("solve in.txt --solver race:backtrack,pack")
+                .unwrap()
+                .options
+                .solve
+                .solver
+                .name(),
+            "race:backtrack,pack"
+        );
+    }
+
+    #[test]
+    fn usage_errors() {
+        for args in [
+            "solve",
+            "solve a b",
+            "render in.txt",
+            "solve in.txt --format yaml",
+            "solve in.txt --solver magic",
+            "solve in.txt --solver race:area,magic",
+            "solve in.txt --threads 0",
+            "solve in.txt --timeout soon",
+            "solve in.txt --checkpoint-every -1",
+            "solve in.txt --colour",
+            "solve in.txt --format",
+            "generate --width 9..3",
+            "unpack in.txt",
+        ] {
+            let err = parse(args).unwrap_err();
+            assert_eq!(err.exit_code(), 2, "{args}");
+        }
+    }
+}
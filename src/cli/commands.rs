@@ -0,0 +1,537 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader, ErrorKind, Read, Write},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use super::*;
+use crate::{
+    binary::{self, BinaryStream, MAGIC},
+    cache::VerdictCache,
+    generator::{self, GeneratorConfig},
+    presents::{
+        OrientationPolicy, PresentPossibilities, ShapeSet,
+        polyominoes::{polyominoes, shape_set},
+    },
+    puzzle::Puzzle,
+    region::RegionSpec,
+    report::{RegionReport, Report, Verdict},
+    solver::{Cancel, Observer, Progress},
+    stream::{solve_regions, solve_stream},
+    tree::Tree,
+};
+
+// Redraws one line on stderr, at most once every `every`
+struct ProgressLine {
+    every: Duration,
+    drawn: Mutex<Option<Instant>>,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+struct Stats {
+    shapes: Vec<ShapeStats>,
+    regions: usize,
+    area_passes: usize,
+    widths: Option<(usize, usize)>,
+    heights: Option<(usize, usize)>,
+    presents: usize,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+struct ShapeStats {
+    size: usize,
+    orientations: usize,
+    symmetry: String,
+    policy: String,
+}
+
+// Text and CSV go out a region at a time, JSON needs the whole report
+pub fn solve<W: Write>(input: &str, options: &Options, output: &mut W) -> Result<(), CliError> {
+    let mut report = Report::default();
+    let mut passed = 0;
+    let mut unproven = 0;
+    let mut total = 0;
+    if options.format == Format::Csv {
+        writeln!(output, "{}", Report::CSV_HEADER)?;
+    }
+
+    let mut config = options.solve.clone();
+    if let Some(path) = &options.cache {
+        config.cache = Some(Arc::new(VerdictCache::load(path)?));
+    }
+    if let Some(dir) = &config.checkpoint_dir {
+        fs::create_dir_all(dir)?;
+    }
+    if let Some(every) = options.progress {
+        config.observer = Some(Arc::new(ProgressLine::new(every)));
+    }
+
+    let on_region = |region: RegionReport| -> io::Result<()> {
+        if options.progress.is_some() {
+            ProgressLine::clear();
+        }
+        total += 1;
+        if region.fits() {
+            passed += 1;
+            if !region.proven {
+                unproven += 1;
+            }
+        }
+        match options.format {
+            Format::Text => writeln!(output, "{region}")?,
+            Format::Csv => writeln!(output, "{}", region.to_csv_row())?,
+            Format::Json => report.regions.push(region),
+        }
+        output.flush()
+    };
+
+    let mut reader = open(input)?;
+    if reader.fill_buf()?.starts_with(MAGIC) {
+        let regions = BinaryStream::new(reader)?;
+        let shapes = ShapeSet::from(&regions.shapes);
+        let regions =
+            regions.map(|region| region.map_err(|err| io::Error::new(ErrorKind::InvalidData, err)));
+        solve_regions(&shapes, regions, &config, on_region)?;
+    } else {
+        solve_stream(reader, &config, on_region)?;
+    }
+    if let (Some(path), Some(cache)) = (&options.cache, &config.cache) {
+        cache.save(path)?;
+    }
+
+    match options.format {
+        Format::Text if unproven > 0 => writeln!(
+            output,
+            "{passed} of {total} regions fit, {unproven} of them unproven"
+        )?,
+        Format::Text => writeln!(output, "{passed} of {total} regions fit")?,
+        Format::Csv => {}
+        Format::Json => writeln!(output, "{}", to_json(&report)?)?,
+    }
+    Ok(())
+}
+
+impl ProgressLine {
+    fn new(every: Duration) -> Self {
+        Self {
+            every,
+            drawn: Mutex::new(None),
+        }
+    }
+
+    fn clear() {
+        eprint!("\r\x1b[K");
+    }
+}
+
+impl Observer for ProgressLine {
+    fn progress(&self, region: usize, progress: &Progress) {
+        let mut drawn = self.drawn.lock().unwrap();
+        if drawn.is_some_and(|drawn| drawn.elapsed() < self.every) {
+            return;
+        }
+        *drawn = Some(Instant::now());
+        Self::clear();
+        eprint!("Region {region}: {progress}");
+    }
+}
+
+pub fn check<W: Write>(input: &str, output: &mut W) -> Result<(), CliError> {
+    let puzzle = load(input)?;
+    let shapes = ShapeSet::from(&puzzle.shapes);
+    for (index, spec) in puzzle.regions.iter().enumerate() {
+        Tree::try_from_spec(spec, &shapes).map_err(|err| CliError::Pin(index, err))?;
+    }
+    writeln!(
+        output,
+        "OK: {} shapes, {} regions",
+        puzzle.shapes.len(),
+        puzzle.regions.len()
+    )?;
+    Ok(())
+}
+
+// With the chosen solver, and no longer than the timeout
+pub fn render<W: Write>(
+    input: &str,
+    region: usize,
+    options: &Options,
+    output: &mut W,
+) -> Result<(), CliError> {
+    let puzzle = load(input)?;
+    let spec = puzzle.regions.get(region).ok_or_else(|| {
+        usage(format!(
+            "There is no region {region}, the puzzle has {}",
+            puzzle.regions.len()
+        ))
+    })?;
+    let tree =
+        Tree::try_from_spec(spec, &puzzle.shapes).map_err(|err| CliError::Pin(region, err))?;
+
+    writeln!(output, "{spec}")?;
+    let deadline = (options.solve.timeout).map(|timeout| Instant::now() + timeout);
+    let outcome = options.solve.solver.solve(&tree, &Cancel::at(deadline));
+    write!(output, "{}", tree.render(spec, &outcome.placements))?;
+    match outcome.verdict {
+        Verdict::Fits if outcome.placements.is_empty() => writeln!(
+            output,
+            "Fits by {}, which doesn't give a packing",
+            outcome.decided_by
+        )?,
+        Verdict::Fits => {}
+        Verdict::DoesNotFit => writeln!(output, "No packing found")?,
+        Verdict::TimedOut => writeln!(output, "Timed out before a packing was found")?,
+        Verdict::Undecided => writeln!(output, "No solver could decide it")?,
+    }
+    Ok(())
+}
+
+pub fn generate<W: Write>(options: &GenerateOptions, output: &mut W) -> Result<(), CliError> {
+    let shapes = match &options.shapes {
+        Some(path) => load(path)?.shapes,
+        None => shape_set(
+            &polyominoes(5, OrientationPolicy::Dihedral, 3),
+            OrientationPolicy::Dihedral,
+        ),
+    };
+    if shapes.is_empty() {
+        return Err(usage("There are no shapes to generate from".to_string()));
+    }
+    let config = GeneratorConfig {
+        seed: options.seed,
+        regions: options.regions,
+        widths: options.widths.0..=options.widths.1,
+        heights: options.heights.0..=options.heights.1,
+        unsolvable_percent: options.unsolvable_percent,
+    };
+
+    let puzzle = generator::generate(&shapes, &config);
+    match &options.output {
+        Some(path) => {
+            puzzle.write(path)?;
+            writeln!(output, "Wrote {} regions to {path}", puzzle.labels.len())?;
+        }
+        None => write!(output, "{}", puzzle.text)?,
+    }
+    Ok(())
+}
+
+pub fn stats<W: Write>(input: &str, options: &Options, output: &mut W) -> Result<(), CliError> {
+    let puzzle = load(input)?;
+
+    let shapes = ShapeSet::from(&puzzle.shapes);
+    let mut area_passes = 0;
+    for (index, spec) in puzzle.regions.iter().enumerate() {
+        let tree = Tree::try_from_spec(spec, &shapes).map_err(|err| CliError::Pin(index, err))?;
+        if tree.simple_check() {
+            area_passes += 1;
+        }
+    }
+    let span = |sizes: Vec<usize>| Some((*sizes.iter().min()?, *sizes.iter().max()?));
+    let stats = Stats {
+        shapes: puzzle
+            .shapes
+            .iter()
+            .map(|shape| ShapeStats {
+                size: shape.get_size(),
+                orientations: shape.possibilities.len(),
+                symmetry: shape.symmetry_group().to_string(),
+                policy: shape.policy.to_string(),
+            })
+            .collect(),
+        regions: puzzle.regions.len(),
+        area_passes,
+        widths: span(puzzle.regions.iter().map(|spec| spec.width).collect()),
+        heights: span(puzzle.regions.iter().map(|spec| spec.height).collect()),
+        presents: puzzle
+            .regions
+            .iter()
+            .map(|spec| spec.demand.iter().sum::<usize>())
+            .sum(),
+    };
+
+    match options.format {
+        Format::Csv => return Err(usage("stats has no csv output".to_string())),
+        Format::Text => {
+            writeln!(output, "{} shapes", stats.shapes.len())?;
+            for (idx, shape) in stats.shapes.iter().enumerate() {
+                writeln!(
+                    output,
+                    "  {idx}: {} cells, {} orientations, symmetry {}, {}",
+                    shape.size, shape.orientations, shape.symmetry, shape.policy
+                )?;
+            }
+            writeln!(
+                output,
+                "{} regions, {} pass the area check, {} presents demanded",
+                stats.regions, stats.area_passes, stats.presents
+            )?;
+            if let (Some(widths), Some(heights)) = (stats.widths, stats.heights) {
+                writeln!(
+                    output,
+                    "widths {}..{}, heights {}..{}",
+                    widths.0, widths.1, heights.0, heights.1
+                )?;
+            }
+        }
+        Format::Json => writeln!(output, "{}", to_json(&stats)?)?,
+    }
+    Ok(())
+}
+
+pub fn convert<W: Write>(input: &str, path: &str, output: &mut W) -> Result<(), CliError> {
+    let bytes = read_all(input)?;
+    if bytes.starts_with(MAGIC) {
+        fs::write(path, binary::binary_to_text(&bytes)?)?;
+        writeln!(output, "Wrote text to {path}")?;
+    } else {
+        fs::write(path, binary::encode(&parse_text(input, bytes)?))?;
+        writeln!(output, "Wrote binary to {path}")?;
+    }
+    Ok(())
+}
+
+fn load(input: &str) -> Result<Puzzle, CliError> {
+    let bytes = read_all(input)?;
+    if bytes.starts_with(MAGIC) {
+        Ok(binary::decode(&bytes)?)
+    } else {
+        parse_text(input, bytes)
+    }
+}
+
+fn parse_text(input: &str, bytes: Vec<u8>) -> Result<Puzzle, CliError> {
+    let text = String::from_utf8(bytes)
+        .map_err(|_| ParseError::new(format!("{input} is neither text nor a binary puzzle")))?;
+    Ok(Puzzle::try_new(&text)?)
+}
+
+fn read_all(input: &str) -> Result<Vec<u8>, CliError> {
+    let mut bytes: Vec<u8> = Vec::new();
+    open(input)?.read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+// "-" reads stdin
+fn open(input: &str) -> Result<Box<dyn BufRead>, CliError> {
+    if input == "-" {
+        return Ok(Box::new(io::stdin().lock()));
+    }
+    let file =
+        File::open(input).map_err(|err| io::Error::new(err.kind(), format!("{input}: {err}")))?;
+    Ok(Box::new(BufReader::new(file)))
+}
+
+#[cfg(feature = "serde")]
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String, CliError> {
+    Ok(serde_json::to_string_pretty(value).unwrap())
+}
+
+// Parsing turns the format down first, this is for anything that gets past
+#[cfg(not(feature = "serde"))]
+fn to_json<T>(value: &T) -> Result<String, CliError> {
+    Err(usage(JSON_NEEDS_SERDE.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(args: &str) -> Result<String, CliError> {
+        let cli = Cli::parse(args.split_whitespace().map(str::to_string))?;
+        let mut output: Vec<u8> = Vec::new();
+        cli.run(&mut output)?;
+        Ok(String::from_utf8(output).unwrap())
+    }
+
+    // CSV rows without the elapsed time, which changes from run to run
+    fn untimed(csv: &str) -> Vec<String> {
+        csv.lines()
+            .map(|line| line.rsplit_once(',').unwrap().0.to_string())
+            .collect()
+    }
+
+    fn temp_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("aoc_2025_12_cli_{name}"));
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn solve() {
+        let output = run("solve data/example.txt").unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert_eq!(lines.len(), 4);
+        // The area check passes all three, though the third doesn't fit
+        assert!(
+            lines[0].starts_with("Region 0 (4x4: 0 0 0 0 2 0) PASS by area (unproven), 0 nodes, ")
+        );
+        assert_eq!(lines[3], "3 of 3 regions fit, 3 of them unproven");
+    }
+
+    #[test]
+    fn solve_csv() {
+        let output = run("solve data/example.txt --format csv").unwrap();
+
+        assert_eq!(
+            untimed(&output),
+            vec![
+                "index,width,height,demand,verdict,decided_by,proven,nodes",
+                "0,4,4,0 0 0 0 2 0,PASS,area,false,0",
+                "1,12,5,1 0 1 0 2 2,PASS,area,false,0",
+                "2,12,5,1 0 1 0 3 2,PASS,area,false,0",
+            ]
+        );
+        assert_eq!(
+            untimed(&run("solve data/example.txt --format csv --threads 2").unwrap()),
+            untimed(&output)
+        );
+    }
+
+    #[test]
+    fn solve_timeout() {
+        let path = temp_path("timeout.txt");
+        fs::write(&path, "0:\n###\n#..\n#..\n\n9x9: 6\n").unwrap();
+
+        let output = run(&format!(
+            "solve {path} --solver backtrack --timeout 0 --format csv"
+        ))
+        .unwrap();
+
+        assert!(untimed(&output)[1].starts_with("0,9,9,6,TIMEOUT,backtrack,false,"));
+    }
+
+    #[test]
+    fn solve_cached() {
+        let input = temp_path("cached.txt");
+        let cache = temp_path("cached.cache");
+        let _ = fs::remove_file(&cache);
+        fs::write(&input, "0:\n###\n#..\n###\n\n4x4: 2\n5x4: 1\n").unwrap();
+
+        let solve = format!("solve {input} --solver backtrack --cache {cache}");
+        let first = run(&solve).unwrap();
+        let second = run(&solve).unwrap();
+
+        assert!(first.lines().next().unwrap().contains("by backtrack"));
+        assert!(first.lines().nth(1).unwrap().contains("by cache"));
+        assert!(second.lines().next().unwrap().contains("by cache"));
+        assert_eq!(fs::read_to_string(&cache).unwrap().lines().count(), 2);
+    }
+
+    #[test]
+    fn parse_errors_fail() {
+        let path = temp_path("bad.txt");
+        fs::write(&path, "0:\n###\n#..\n#..\n\n4x4: 1\n4x4: one\n").unwrap();
+
+        for command in ["solve", "check", "stats"] {
+            let err = run(&format!("{command} {path}")).unwrap_err();
+            assert_eq!(err.exit_code(), 1, "{command}");
+        }
+
+        let err = run("check data/no_such_file.txt").unwrap_err();
+        assert!(err.to_string().contains("data/no_such_file.txt"));
+    }
+
+    #[test]
+    fn check() {
+        assert_eq!(
+            run("check data/example.txt").unwrap(),
+            "OK: 6 shapes, 3 regions\n"
+        );
+
+        let path = temp_path("pinned.txt");
+        fs::write(&path, "0:\n###\n#..\n#..\n\n4x4: 1 | pinned 0:0@2,2\n").unwrap();
+        let err = run(&format!("check {path}")).unwrap_err();
+        assert!(matches!(err, CliError::Pin(0, PinError::OutOfBounds(_))));
+    }
+
+    #[test]
+    fn render() {
+        let output = run("render data/example.txt --region 0").unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert_eq!(lines[0], "4x4: 0 0 0 0 2 0");
+        assert_eq!(lines.len(), 5);
+        assert_eq!(output.matches('.').count(), 2);
+        assert_eq!(
+            run("render data/example.txt --region 3")
+                .unwrap_err()
+                .exit_code(),
+            2
+        );
+
+        let path = temp_path("render_timeout.txt");
+        fs::write(&path, "0:\n###\n#..\n#..\n\n9x9: 6\n").unwrap();
+        let output = run(&format!("render {path} --region 0 --timeout 0")).unwrap();
+        assert!(output.ends_with("Timed out before a packing was found\n"));
+        let output = run("render data/example.txt --region 0 --solver area").unwrap();
+        assert!(output.ends_with("Fits by area, which doesn't give a packing\n"));
+    }
+
+    #[test]
+    fn generate_and_stats() {
+        let path = temp_path("generated.txt");
+        let output = run(&format!("generate --seed 3 --regions 4 --out {path}")).unwrap();
+        assert_eq!(output, format!("Wrote 4 regions to {path}\n"));
+
+        let stats = run(&format!("stats {path}")).unwrap();
+        assert!(stats.starts_with("8 shapes\n  0: 5 cells, "));
+        assert!(stats.contains("\n4 regions, "));
+
+        assert_eq!(
+            run("generate --seed 3 --regions 4").unwrap(),
+            fs::read_to_string(&path).unwrap()
+        );
+    }
+
+    #[test]
+    fn convert() {
+        let binary = temp_path("example.bin");
+        let text = temp_path("example.txt");
+
+        run(&format!("convert data/example.txt {binary}")).unwrap();
+        assert!(fs::read(&binary).unwrap().starts_with(MAGIC));
+        assert_eq!(
+            untimed(&run(&format!("solve {binary} --format csv")).unwrap()),
+            untimed(&run("solve data/example.txt --format csv").unwrap())
+        );
+
+        let corrupt = temp_path("corrupt.bin");
+        let mut bytes = fs::read(&binary).unwrap();
+        bytes.truncate(bytes.len() - 2);
+        fs::write(&corrupt, bytes).unwrap();
+        let err = run(&format!("solve {corrupt} --format csv")).unwrap_err();
+        assert!(err.to_string().contains("ends early"), "{err}");
+
+        run(&format!("convert {binary} {text}")).unwrap();
+        assert_eq!(
+            Puzzle::new(&fs::read_to_string(&text).unwrap()),
+            Puzzle::new(&fs::read_to_string("data/example.txt").unwrap())
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json() {
+        let output = run("solve data/example.txt --format json").unwrap();
+        let report = crate::json::report_from_json(&output).unwrap();
+
+        assert_eq!(report.regions.len(), 3);
+        assert_eq!(report.regions[2].index, 2);
+
+        let stats: serde_json::Value =
+            serde_json::from_str(&run("stats data/example.txt --format json").unwrap()).unwrap();
+        assert_eq!(stats["regions"], 3);
+    }
+
+    #[cfg(not(feature = "serde"))]
+    #[test]
+    fn json_needs_serde() {
+        let err = run("solve data/example.txt --format json").unwrap_err();
+
+        assert_eq!(err.exit_code(), 2);
+    }
+}
@@ -0,0 +1,402 @@
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    fnv::fnv1a,
+    presents::ShapeSet,
+    puzzle::Puzzle,
+    region::RegionSpec,
+    report::Verdict,
+    solver::{Cancel, Registry},
+    tree::{CertificateError, PinError, Tree},
+};
+
+// Runs every solver in a registry on the same regions and holds what they
+// say to each other, to what the region is known to be when that's known,
+// and every packing they claim to the certificate checker. Only verdicts a
+// solver is sound for count, so the area check's refutations are held to
+// the rest but its guesses that a region fits are not. A region they get
+// wrong can be shrunk while it still goes wrong, and saved as a puzzle that
+// `solve` replays.
+pub struct Harness {
+    registry: Registry,
+    shapes: ShapeSet,
+    // Per solver and region; a solver that runs out has no say
+    timeout: Duration,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Disagreement {
+    pub spec: RegionSpec,
+    // What the region was built to be, if anything
+    pub known: Option<Verdict>,
+    // Every decided verdict its solver is sound for, by solver
+    pub verdicts: Vec<(String, Verdict)>,
+    // Solvers whose packing didn't check out, and why
+    pub bad_certificates: Vec<(String, CertificateError)>,
+}
+
+impl Harness {
+    pub fn new(registry: Registry, shapes: ShapeSet, timeout: Duration) -> Self {
+        Self {
+            registry,
+            shapes,
+            timeout,
+        }
+    }
+
+    // None when everyone agrees. A region whose pins don't fit it can't be
+    // asked about at all.
+    pub fn compare(
+        &self,
+        spec: &RegionSpec,
+        known: Option<Verdict>,
+    ) -> Result<Option<Disagreement>, PinError> {
+        let tree = Tree::try_from_spec(spec, &self.shapes)?;
+        let mut disagreement = Disagreement {
+            spec: spec.clone(),
+            known,
+            verdicts: vec![],
+            bad_certificates: vec![],
+        };
+
+        for name in self.registry.names() {
+            let solver = self.registry.get(name).unwrap();
+            let outcome = solver.solve(&tree, &Cancel::at(Some(Instant::now() + self.timeout)));
+            if !solver.is_sound(outcome.verdict) {
+                continue;
+            }
+            if outcome.verdict == Verdict::Fits
+                && let Err(err) = tree.check_certificate(spec, &outcome.placements)
+            {
+                disagreement.bad_certificates.push((name.to_string(), err));
+            }
+            disagreement
+                .verdicts
+                .push((name.to_string(), outcome.verdict));
+        }
+
+        let mut verdicts = known
+            .iter()
+            .chain(disagreement.verdicts.iter().map(|(_, v)| v));
+        let first = verdicts.next();
+        let agree = verdicts.all(|verdict| Some(verdict) == first);
+        if agree && disagreement.bad_certificates.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(disagreement))
+        }
+    }
+
+    // Takes away presents, blocked cells, pins, rows and columns one at a
+    // time for as long as the solvers still disagree. Fewer presents, blocked
+    // cells or pins still fit when the whole did, and a smaller region still
+    // doesn't fit when the whole didn't, so what is known carries over when
+    // it can.
+    pub fn minimise(&self, mut disagreement: Disagreement) -> Disagreement {
+        'shrink: loop {
+            for (spec, known) in self.smaller(&disagreement.spec, disagreement.known) {
+                if let Ok(Some(smaller)) = self.compare(&spec, known) {
+                    disagreement = smaller;
+                    continue 'shrink;
+                }
+            }
+            return disagreement;
+        }
+    }
+
+    // As a one-region puzzle in `dir`, named after what's in it so the same
+    // region is only saved once, whichever toolchain built the harness
+    pub fn save(&self, disagreement: &Disagreement, dir: &Path) -> io::Result<PathBuf> {
+        let puzzle = Puzzle {
+            shapes: self.shapes.to_vec(),
+            regions: vec![disagreement.spec.clone()],
+        };
+        let text = puzzle.to_string();
+
+        fs::create_dir_all(dir)?;
+        let path = dir.join(format!("{:016x}.txt", fnv1a(text.bytes())));
+        fs::write(&path, text)?;
+        Ok(path)
+    }
+
+    // Every region one step smaller than `spec`, with what is still known
+    // about it. Pins are only ever let go of: a region is made smaller
+    // without those that would no longer land inside it, and a present is
+    // only taken away while more are demanded than pinned.
+    fn smaller(
+        &self,
+        spec: &RegionSpec,
+        known: Option<Verdict>,
+    ) -> Vec<(RegionSpec, Option<Verdict>)> {
+        let still = |verdict: Verdict| known.filter(|&known| known == verdict);
+        let mut specs: Vec<(RegionSpec, Option<Verdict>)> = Vec::new();
+
+        for idx in 0..spec.demand.len() {
+            let pinned = (spec.pinned.iter())
+                .filter(|pin| pin.present_idx == idx)
+                .count();
+            if spec.demand[idx] > pinned {
+                let mut fewer = spec.clone();
+                fewer.demand[idx] -= 1;
+                specs.push((fewer, still(Verdict::Fits)));
+            }
+        }
+        for idx in 0..spec.blocked.len() {
+            let mut unblocked = spec.clone();
+            unblocked.blocked.remove(idx);
+            specs.push((unblocked, still(Verdict::Fits)));
+        }
+        for idx in 0..spec.pinned.len() {
+            let mut unpinned = spec.clone();
+            unpinned.pinned.remove(idx);
+            specs.push((unpinned, still(Verdict::Fits)));
+        }
+        if spec.width > 1 {
+            let mut narrower = spec.clone();
+            narrower.width -= 1;
+            narrower.blocked.retain(|&(x, _)| x < narrower.width);
+            specs.push(self.without_stray_pins(narrower, known));
+        }
+        if spec.height > 1 {
+            let mut shorter = spec.clone();
+            shorter.height -= 1;
+            shorter.blocked.retain(|&(_, y)| y < shorter.height);
+            specs.push(self.without_stray_pins(shorter, known));
+        }
+        specs
+    }
+
+    // A region cut down from one that didn't fit doesn't either, unless a
+    // pin had to go with the cells it stood on
+    fn without_stray_pins(
+        &self,
+        mut spec: RegionSpec,
+        known: Option<Verdict>,
+    ) -> (RegionSpec, Option<Verdict>) {
+        let pinned = spec.pinned.len();
+        let (width, height) = (spec.width, spec.height);
+        spec.pinned.retain(|pin| {
+            (self.shapes.get(pin.present_idx))
+                .and_then(|shape| shape.possibilities.get(pin.poss_idx))
+                .is_some_and(|possibility| pin.lands_inside(possibility, width, height))
+        });
+        let known =
+            known.filter(|&known| known == Verdict::DoesNotFit && spec.pinned.len() == pinned);
+        (spec, known)
+    }
+}
+
+mod formatting {
+    use super::*;
+
+    impl fmt::Display for Disagreement {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "Solvers disagree on \"{}\":", self.spec)?;
+            if let Some(known) = self.known {
+                write!(f, " known {known},")?;
+            }
+            for (name, verdict) in &self.verdicts {
+                write!(f, " {name} {verdict}")?;
+            }
+            for (name, err) in &self.bad_certificates {
+                write!(f, "; {name}: {err}")?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        generator::{GeneratorConfig, generate},
+        presents::{OrientationPolicy, PresentPossibilities},
+        region::Pin,
+        rng::Rng,
+        solver::{Outcome, Solver},
+    };
+    use std::sync::Arc;
+
+    // Where `solvers_agree` leaves what it finds, for `regressions_stay_fixed`
+    const REGRESSIONS: &str = "data/regressions";
+
+    fn shapes() -> ShapeSet {
+        ShapeSet::new(vec![
+            PresentPossibilities::new("###\n#..\n#.."),
+            PresentPossibilities::new("##.\n.##\n..."),
+            PresentPossibilities::new("###\n#.#\n..."),
+            PresentPossibilities::with_policy("##.\n#..\n...", OrientationPolicy::Rotations),
+        ])
+    }
+
+    fn harness(registry: Registry) -> Harness {
+        Harness::new(registry, shapes(), Duration::from_secs(5))
+    }
+
+    // Regions built to fit, and the same with one more present, which may
+    // or may not
+    #[test]
+    fn solvers_agree() {
+        let harness = harness(Registry::default());
+        let generated = generate(
+            &harness.shapes,
+            &GeneratorConfig {
+                seed: 50,
+                regions: 40,
+                widths: 3..=6,
+                heights: 3..=6,
+                unsolvable_percent: 0,
+            },
+        );
+        let puzzle = Puzzle::new(&generated.text);
+        let mut rng = Rng::new(50);
+
+        for spec in &puzzle.regions {
+            let mut more = spec.clone();
+            more.demand[rng.below(spec.demand.len())] += 1;
+            for (spec, known) in [(spec.clone(), Some(Verdict::Fits)), (more, None)] {
+                if let Some(disagreement) = harness.compare(&spec, known).unwrap() {
+                    let disagreement = harness.minimise(disagreement);
+                    let path = harness.save(&disagreement, Path::new(REGRESSIONS));
+                    panic!("{disagreement}, saved to {path:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn regressions_stay_fixed() {
+        let Ok(entries) = fs::read_dir(REGRESSIONS) else {
+            return;
+        };
+        for entry in entries {
+            let path = entry.unwrap().path();
+            if path.extension().is_none_or(|extension| extension != "txt") {
+                continue;
+            }
+            let puzzle = Puzzle::new(&fs::read_to_string(&path).unwrap());
+            let harness = Harness::new(
+                Registry::default(),
+                ShapeSet::new(puzzle.shapes),
+                Duration::from_secs(5),
+            );
+            for spec in &puzzle.regions {
+                // A region that can't even be built is a broken regression
+                assert_eq!(harness.compare(spec, None), Ok(None), "{path:?}");
+            }
+        }
+    }
+
+    // Claims everything fits, without a packing to show for it
+    struct Boastful;
+
+    impl Solver for Boastful {
+        fn name(&self) -> &str {
+            "boastful"
+        }
+
+        fn solve(&self, _tree: &Tree, _cancel: &Cancel) -> Outcome {
+            Outcome::new(Verdict::Fits, self.name())
+        }
+    }
+
+    // Rules everything out, and admits it's guessing
+    struct Gloomy;
+
+    impl Solver for Gloomy {
+        fn name(&self) -> &str {
+            "gloomy"
+        }
+
+        fn is_exact(&self) -> bool {
+            false
+        }
+
+        fn solve(&self, _tree: &Tree, _cancel: &Cancel) -> Outcome {
+            Outcome::new(Verdict::DoesNotFit, self.name())
+        }
+    }
+
+    #[test]
+    fn inexact_refutations_count() {
+        // The area check passes two S shapes in 3x3, which can't both go in,
+        // and that guess is left out
+        let spec = RegionSpec::new("3x3: 0 2 0 0");
+        assert!(Tree::from_spec(&spec, shapes()).simple_check());
+        assert_eq!(harness(Registry::default()).compare(&spec, None), Ok(None));
+
+        let mut registry = Registry::default();
+        registry.register(Arc::new(Gloomy));
+        let disagreement = harness(registry)
+            .compare(&RegionSpec::new("3x3: 0 0 0 1"), None)
+            .unwrap()
+            .unwrap();
+        let names: Vec<&str> = (disagreement.verdicts.iter())
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(names, vec!["backtrack", "pack", "gloomy"]);
+        assert_eq!(disagreement.verdicts[2].1, Verdict::DoesNotFit);
+    }
+
+    #[test]
+    fn catches_and_shrinks() {
+        let mut registry = Registry::default();
+        registry.register(Arc::new(Boastful));
+        let harness = harness(registry);
+
+        let spec = RegionSpec::new("6x5: 2 1 0 1 | blocked 0,0 5,4");
+        let disagreement = harness.compare(&spec, None).unwrap().unwrap();
+        assert_eq!(disagreement.bad_certificates[0].0, "boastful");
+
+        // Down to a single present it can't show a packing for
+        let minimised = harness.minimise(disagreement);
+        assert_eq!(minimised.spec.demand.iter().sum::<usize>(), 1);
+        assert!(minimised.spec.blocked.is_empty());
+        assert!(minimised.spec.width * minimised.spec.height <= 9);
+
+        let dir = std::env::temp_dir().join(format!("regressions-{}", std::process::id()));
+        let path = harness.save(&minimised, &dir).unwrap();
+        let saved = Puzzle::new(&fs::read_to_string(path).unwrap());
+        assert_eq!(saved.regions, vec![minimised.spec]);
+        assert_eq!(saved.shapes, harness.shapes.to_vec());
+    }
+
+    #[test]
+    fn shrinks_past_pins() {
+        let mut registry = Registry::default();
+        registry.register(Arc::new(Boastful));
+        let harness = harness(registry);
+
+        let spec = RegionSpec::new("6x6: 2 1 0 0 | pinned 0:0@3,3 1:0@0,0");
+        let disagreement = harness.compare(&spec, None).unwrap().unwrap();
+
+        let minimised = harness.minimise(disagreement);
+        assert!(minimised.spec.pinned.is_empty());
+        assert_eq!(minimised.spec.demand.iter().sum::<usize>(), 1);
+        assert!(minimised.spec.width * minimised.spec.height <= 9);
+
+        // The pin at 3,3 no longer lands inside once a column goes
+        let mut narrower = spec.clone();
+        narrower.width = 5;
+        let (spec, known) = harness.without_stray_pins(narrower, Some(Verdict::DoesNotFit));
+        assert_eq!(spec.pinned, vec![Pin::new("1:0@0,0")]);
+        assert_eq!(known, None);
+    }
+
+    #[test]
+    fn unbuildable_regions_fail() {
+        let harness = harness(Registry::default());
+
+        let spec = RegionSpec::new("4x4: 1 0 0 0 | pinned 0:0@5,5");
+
+        assert!(matches!(
+            harness.compare(&spec, None),
+            Err(PinError::OutOfBounds(_))
+        ));
+    }
+}
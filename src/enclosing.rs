@@ -0,0 +1,130 @@
+use crate::{
+    presents::PresentPossibilities,
+    tree::{Packing, Tree},
+};
+
+#[derive(Debug)]
+pub struct Enclosure {
+    pub width: usize,
+    pub height: usize,
+    pub packing: Packing,
+}
+
+// Smallest-area rectangle that packs the whole demand, narrowest side last
+pub fn smallest_rectangle(
+    present_types: &Vec<PresentPossibilities>,
+    demand: &[usize],
+) -> Enclosure {
+    let total_area = demanded_area(present_types, demand);
+
+    for area in total_area.max(1).. {
+        for height in 1..=area.isqrt() {
+            if area % height != 0 {
+                continue;
+            }
+            if let Some(enclosure) = try_size(present_types, demand, area / height, height) {
+                return enclosure;
+            }
+            // Shapes with an orientation policy can care which way round it is
+            if !present_types.iter().all(|p| p.is_transpose_closed())
+                && let Some(enclosure) = try_size(present_types, demand, height, area / height)
+            {
+                return enclosure;
+            }
+        }
+    }
+    unreachable!()
+}
+
+pub fn smallest_square(present_types: &Vec<PresentPossibilities>, demand: &[usize]) -> Enclosure {
+    let total_area = demanded_area(present_types, demand);
+
+    let mut side = total_area.isqrt();
+    if side * side < total_area {
+        side += 1;
+    }
+    for side in side.max(1).. {
+        if let Some(enclosure) = try_size(present_types, demand, side, side) {
+            return enclosure;
+        }
+    }
+    unreachable!()
+}
+
+fn demanded_area(present_types: &[PresentPossibilities], demand: &[usize]) -> usize {
+    assert_eq!(present_types.len(), demand.len());
+    present_types
+        .iter()
+        .zip(demand)
+        .map(|(present, count)| present.get_size() * count)
+        .sum()
+}
+
+fn try_size(
+    present_types: &Vec<PresentPossibilities>,
+    demand: &[usize],
+    width: usize,
+    height: usize,
+) -> Option<Enclosure> {
+    let tree = Tree::with_size(width, height, demand.to_vec(), present_types);
+    let packing = tree.pack()?;
+    Some(Enclosure {
+        width,
+        height,
+        packing,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::presents::OrientationPolicy;
+
+    #[test]
+    fn rectangle() {
+        let poss_vec = vec![
+            PresentPossibilities::new("###\n#.#\n###"),
+            PresentPossibilities::new("##.\n...\n..."),
+        ];
+
+        let enclosure = smallest_rectangle(&poss_vec, &[2, 0]);
+
+        assert_eq!((enclosure.width, enclosure.height), (6, 3));
+        assert_eq!(enclosure.packing.counts, vec![2, 0]);
+    }
+
+    #[test]
+    fn rectangle_exact_area() {
+        let poss_vec = vec![PresentPossibilities::new("###\n###\n###")];
+
+        let enclosure = smallest_rectangle(&poss_vec, &[3]);
+
+        assert_eq!((enclosure.width, enclosure.height), (9, 3));
+        assert_eq!(enclosure.packing.placements.len(), 3);
+    }
+
+    #[test]
+    fn rectangle_fixed_orientation() {
+        let fixed = vec![PresentPossibilities::with_policy(
+            ".#.\n.#.\n.#.",
+            OrientationPolicy::Fixed,
+        )];
+        let dihedral = vec![PresentPossibilities::new(".#.\n.#.\n.#.")];
+
+        let enclosure = smallest_rectangle(&fixed, &[2]);
+        assert_eq!((enclosure.width, enclosure.height), (1, 6));
+
+        let enclosure = smallest_rectangle(&dihedral, &[2]);
+        assert_eq!((enclosure.width, enclosure.height), (6, 1));
+    }
+
+    #[test]
+    fn square() {
+        let poss_vec = vec![PresentPossibilities::new("###\n#.#\n###")];
+
+        let enclosure = smallest_square(&poss_vec, &[2]);
+
+        assert_eq!((enclosure.width, enclosure.height), (6, 6));
+        assert_eq!(enclosure.packing.counts, vec![2]);
+    }
+}
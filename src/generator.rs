@@ -0,0 +1,213 @@
+use std::{fs, io, ops::RangeInclusive};
+
+use crate::{
+    presents::{OrientationPolicy, PresentPossibilities},
+    rng::Rng,
+    space::Space,
+};
+
+#[derive(Clone, Debug)]
+pub struct GeneratorConfig {
+    pub seed: u64,
+    pub regions: usize,
+    pub widths: RangeInclusive<usize>,
+    pub heights: RangeInclusive<usize>,
+    // Out of 100, how many regions are built to just miss
+    pub unsolvable_percent: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GeneratedPuzzle {
+    pub text: String,
+    // Ground truth per region, in input order
+    pub labels: Vec<bool>,
+}
+
+impl GeneratedPuzzle {
+    // Writes the puzzle to `path` and its labels to `path` + ".labels",
+    // one PASS or FAIL per region
+    pub fn write(&self, path: &str) -> io::Result<()> {
+        fs::write(path, &self.text)?;
+        let labels: String = self
+            .labels
+            .iter()
+            .map(|&fits| if fits { "PASS\n" } else { "FAIL\n" })
+            .collect();
+        fs::write(format!("{path}.labels"), labels)
+    }
+}
+
+// Solvable regions come from packing presents at random and demanding
+// exactly what got placed. Unsolvable ones then keep adding presents until
+// the demand is one present past the area of the region.
+pub fn generate(shapes: &[PresentPossibilities], config: &GeneratorConfig) -> GeneratedPuzzle {
+    assert!(!shapes.is_empty());
+    let mut rng = Rng::new(config.seed);
+
+    let mut text = String::new();
+    for (idx, shape) in shapes.iter().enumerate() {
+        match shape.policy {
+            OrientationPolicy::Dihedral => text.push_str(&format!("{idx}:\n")),
+            policy => text.push_str(&format!("{idx}: {policy}\n")),
+        }
+        text.push_str(&format!("{}\n", shape.possibilities[0]));
+    }
+
+    let mut labels: Vec<bool> = Vec::with_capacity(config.regions);
+    for _ in 0..config.regions {
+        let width = rng.between(*config.widths.start(), *config.widths.end());
+        let height = rng.between(*config.heights.start(), *config.heights.end());
+        let mut demand = random_packing(shapes, width, height, &mut rng);
+
+        let fits = !rng.chance(config.unsolvable_percent, 100);
+        if !fits {
+            let area = |demand: &[usize]| -> usize {
+                shapes
+                    .iter()
+                    .zip(demand)
+                    .map(|(shape, count)| shape.get_size() * count)
+                    .sum()
+            };
+            while area(&demand) <= width * height {
+                demand[rng.below(shapes.len())] += 1;
+            }
+        }
+
+        let counts: Vec<String> = demand.iter().map(|count| count.to_string()).collect();
+        text.push_str(&format!("{width}x{height}: {}\n", counts.join(" ")));
+        labels.push(fits);
+    }
+
+    GeneratedPuzzle { text, labels }
+}
+
+fn random_packing(
+    shapes: &[PresentPossibilities],
+    width: usize,
+    height: usize,
+    rng: &mut Rng,
+) -> Vec<usize> {
+    let mut occupied = vec![vec![false; width]; height];
+    let mut demand = vec![0; shapes.len()];
+
+    // The 3x3 box can hang off the edge as long as the present itself doesn't
+    for _ in 0..(4 * width * height) {
+        let present_idx = rng.below(shapes.len());
+        let possibilities = &shapes[present_idx].possibilities;
+        let possibility = &possibilities[rng.below(possibilities.len())];
+        let top = rng.below(height + 2) as isize - 2;
+        let left = rng.below(width + 2) as isize - 2;
+
+        let mut cells: Vec<(usize, usize)> = Vec::new();
+        let mut fits = true;
+        for (row_idx, row) in possibility.spaces.iter().enumerate() {
+            for (col_idx, space) in row.iter().enumerate() {
+                if *space != Space::Occupied {
+                    continue;
+                }
+                let row = top + row_idx as isize;
+                let col = left + col_idx as isize;
+                let on_grid = row >= 0 && col >= 0 && row < height as isize && col < width as isize;
+                if on_grid && !occupied[row as usize][col as usize] {
+                    cells.push((row as usize, col as usize));
+                } else {
+                    fits = false;
+                }
+            }
+        }
+
+        if fits {
+            for (row, col) in cells {
+                occupied[row][col] = true;
+            }
+            demand[present_idx] += 1;
+        }
+    }
+
+    demand
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{region::RegionSpec, tree::Tree};
+
+    fn shapes() -> Vec<PresentPossibilities> {
+        vec![
+            PresentPossibilities::new("###\n##.\n##."),
+            PresentPossibilities::new("###\n.#.\n###"),
+            PresentPossibilities::with_policy("##.\n...\n...", OrientationPolicy::Fixed),
+        ]
+    }
+
+    fn config(seed: u64) -> GeneratorConfig {
+        GeneratorConfig {
+            seed,
+            regions: 20,
+            widths: 4..=8,
+            heights: 3..=6,
+            unsolvable_percent: 40,
+        }
+    }
+
+    #[test]
+    fn deterministic() {
+        let shapes = shapes();
+
+        assert_eq!(generate(&shapes, &config(7)), generate(&shapes, &config(7)));
+        assert_ne!(generate(&shapes, &config(7)), generate(&shapes, &config(8)));
+    }
+
+    #[test]
+    fn format() {
+        let shapes = shapes();
+        let puzzle = generate(&shapes, &config(3));
+
+        let sections: Vec<&str> = puzzle.text.trim_end().split("\n\n").collect();
+        assert_eq!(sections.len(), 4);
+        assert_eq!(sections[0], "0:\n###\n##.\n##.");
+        assert_eq!(sections[2], "2: fixed\n##.\n...\n...");
+        assert_eq!(sections[3].lines().count(), 20);
+        assert_eq!(puzzle.labels.len(), 20);
+        assert!(puzzle.labels.contains(&true));
+        assert!(puzzle.labels.contains(&false));
+    }
+
+    #[test]
+    fn labels_hold() {
+        let shapes = shapes();
+        let puzzle = generate(&shapes, &config(11));
+        let regions = puzzle.text.trim_end().split("\n\n").last().unwrap();
+
+        for (line, fits) in regions.lines().zip(&puzzle.labels) {
+            let spec = RegionSpec::new(line);
+            let area: usize = shapes
+                .iter()
+                .zip(&spec.demand)
+                .map(|(shape, count)| shape.get_size() * count)
+                .sum();
+            assert!(spec.demand.iter().sum::<usize>() > 0);
+            if *fits {
+                assert!(area <= spec.free_cells(), "{line}");
+            } else {
+                assert!(area > spec.free_cells(), "{line}");
+                assert!(!Tree::from_spec(&spec, &shapes).simple_check(), "{line}");
+            }
+        }
+    }
+
+    #[test]
+    fn write() {
+        let shapes = shapes();
+        let puzzle = generate(&shapes, &config(5));
+        let path = std::env::temp_dir().join("aoc_2025_12_generator_write.txt");
+        let path = path.to_str().unwrap();
+
+        puzzle.write(path).unwrap();
+
+        assert_eq!(fs::read_to_string(path).unwrap(), puzzle.text);
+        let labels = fs::read_to_string(format!("{path}.labels")).unwrap();
+        assert_eq!(labels.lines().count(), 20);
+        assert_eq!(labels.lines().next() == Some("PASS"), puzzle.labels[0]);
+    }
+}
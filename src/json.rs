@@ -0,0 +1,206 @@
+use crate::{puzzle::Puzzle, report::Report};
+
+pub const PUZZLE_SCHEMA: &str = include_str!("../data/schema/puzzle.schema.json");
+pub const REPORT_SCHEMA: &str = include_str!("../data/schema/report.schema.json");
+
+pub fn puzzle_to_json(puzzle: &Puzzle) -> String {
+    serde_json::to_string_pretty(puzzle).unwrap()
+}
+
+pub fn puzzle_from_json(input: &str) -> serde_json::Result<Puzzle> {
+    serde_json::from_str(input)
+}
+
+pub fn report_to_json(report: &Report) -> String {
+    serde_json::to_string_pretty(report).unwrap()
+}
+
+pub fn report_from_json(input: &str) -> serde_json::Result<Report> {
+    serde_json::from_str(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        file_parser::FileParser,
+        presents::PresentPossibilities,
+        presents::ShapeSet,
+        region::RegionSpec,
+        report::{RegionReport, Verdict},
+        solver::{BranchAndBound, Prunes, SolveConfig, solve_region},
+        tree::{Placement, Tree},
+    };
+    use std::sync::Arc;
+
+    #[test]
+    fn puzzle_round_trip() {
+        let my_file = FileParser::new("data/example.txt");
+        let puzzle = Puzzle::new(my_file.get_str());
+
+        let json = puzzle_to_json(&puzzle);
+
+        assert_eq!(puzzle_from_json(&json).unwrap(), puzzle);
+    }
+
+    #[test]
+    fn puzzle_shape() {
+        let puzzle =
+            Puzzle::new("0: rotations\n###\n#..\n###\n\n5x3: 1 | blocked 4,2 | pinned 0:1@0,0");
+
+        let json: serde_json::Value = serde_json::from_str(&puzzle_to_json(&puzzle)).unwrap();
+
+        let shape = &json["shapes"][0];
+        assert_eq!(shape["policy"], "rotations");
+        assert_eq!(shape["shape"]["spaces"][1][1], ".");
+        assert_eq!(shape["shape"]["spaces"][1][0], "#");
+        let region = &json["regions"][0];
+        assert_eq!(region["width"], 5);
+        assert_eq!(region["blocked"][0], serde_json::json!([4, 2]));
+        assert_eq!(region["pinned"][0]["poss_idx"], 1);
+    }
+
+    #[test]
+    fn shapes_rebuild_orientations() {
+        let json = r##"{"shape": {"spaces": [["#", "#", "."], [".", ".", "."], [".", ".", "."]]},
+                        "policy": "dihedral"}"##;
+
+        let shape: PresentPossibilities = serde_json::from_str(json).unwrap();
+
+        assert_eq!(shape, PresentPossibilities::new("##.\n...\n..."));
+    }
+
+    #[test]
+    fn puzzle_rejections() {
+        let puzzle = Puzzle::new("0:\n#..\n...\n...\n\n4x4: 1");
+        let json: serde_json::Value = serde_json::from_str(&puzzle_to_json(&puzzle)).unwrap();
+        let pin = serde_json::json!([{"present_idx": 1, "poss_idx": 0, "x": 0, "y": 0}]);
+
+        for (pointer, value, expected) in [
+            (
+                "/shapes/0/shape/spaces/0/0",
+                serde_json::json!("."),
+                "no occupied cells",
+            ),
+            (
+                "/regions/0/blocked",
+                serde_json::json!([[4, 0]]),
+                "outside the region",
+            ),
+            (
+                "/regions/0/demand",
+                serde_json::json!([1, 0]),
+                "2 counts for 1 shapes",
+            ),
+            ("/regions/0/pinned", pin, "unknown present"),
+        ] {
+            let mut broken = json.clone();
+            *broken.pointer_mut(pointer).unwrap() = value;
+            let err = puzzle_from_json(&broken.to_string())
+                .unwrap_err()
+                .to_string();
+            assert!(err.contains(expected), "{pointer}: {err}");
+        }
+    }
+
+    #[test]
+    fn report_round_trip() {
+        let poss_vec = vec![PresentPossibilities::new("###\n#..\n###")];
+        let tree = Tree::new("4x4: 2", &poss_vec);
+        let packing = tree.pack().unwrap();
+        let report = Report {
+            regions: vec![
+                RegionReport {
+                    index: 0,
+                    width: 4,
+                    height: 4,
+                    demand: vec![2],
+                    verdict: Verdict::Fits,
+                    decided_by: "pack".to_string(),
+                    proven: true,
+                    nodes: 9,
+                    pruned: Prunes::default(),
+                    placements: (packing.placements.iter())
+                        .map(|placement| tree.region_placement(placement))
+                        .collect(),
+                    elapsed_micros: 12,
+                },
+                RegionReport {
+                    index: 1,
+                    width: 9,
+                    height: 9,
+                    demand: vec![6],
+                    verdict: Verdict::TimedOut,
+                    decided_by: "backtrack".to_string(),
+                    proven: false,
+                    nodes: 2,
+                    pruned: Prunes::default(),
+                    placements: vec![],
+                    elapsed_micros: 3,
+                },
+            ],
+        };
+
+        let json = report_to_json(&report);
+
+        assert_eq!(report_from_json(&json).unwrap(), report);
+        assert!(json.contains("\"x\": "));
+        assert!(json.contains("\"verdict\": \"timed_out\""));
+        let _: Placement =
+            serde_json::from_str(r#"{"present_idx":0,"poss_idx":1,"pos_x":2,"pos_y":3}"#).unwrap();
+    }
+
+    #[test]
+    fn schemas_are_json() {
+        for schema in [PUZZLE_SCHEMA, REPORT_SCHEMA] {
+            let value: serde_json::Value = serde_json::from_str(schema).unwrap();
+            assert_eq!(value["type"], "object");
+        }
+    }
+
+    // Every key a report writes is described, and required, by the schema
+    #[test]
+    fn report_schema_matches() {
+        let schema: serde_json::Value = serde_json::from_str(REPORT_SCHEMA).unwrap();
+        let poss_vec = vec![PresentPossibilities::new("###\n#..\n###")];
+        let config = SolveConfig {
+            solver: Arc::new(BranchAndBound),
+            ..SolveConfig::default()
+        };
+        let report = Report {
+            regions: vec![
+                solve_region(
+                    0,
+                    &RegionSpec::new("4x4: 2"),
+                    &ShapeSet::new(poss_vec),
+                    &config,
+                )
+                .unwrap(),
+            ],
+        };
+        let json: serde_json::Value = serde_json::from_str(&report_to_json(&report)).unwrap();
+
+        let keys = |value: &serde_json::Value| -> Vec<String> {
+            let mut keys: Vec<String> = value.as_object().unwrap().keys().cloned().collect();
+            keys.sort();
+            keys
+        };
+        let described = |name: &str| -> (Vec<String>, Vec<String>) {
+            let def = &schema["$defs"][name];
+            let mut required: Vec<String> = (def["required"].as_array().unwrap().iter())
+                .map(|key| key.as_str().unwrap().to_string())
+                .collect();
+            required.sort();
+            (keys(&def["properties"]), required)
+        };
+
+        let region = &json["regions"][0];
+        for (name, value) in [
+            ("region", region),
+            ("prunes", &region["pruned"]),
+            ("placement", &region["placements"][0]),
+        ] {
+            assert_eq!(described(name), (keys(value), keys(value)), "{name}");
+        }
+    }
+}
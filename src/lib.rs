@@ -1,36 +1,40 @@
 pub mod file_parser;
-use crate::{file_parser::FileParser, presents::PresentPossibilities, tree::Tree};
+use crate::{file_parser::FileParser, report::Report, solver::SolveConfig, stream::solve_stream};
 
+pub mod binary;
+pub mod cache;
+pub mod cli;
+pub mod differential;
+pub mod enclosing;
+pub mod fnv;
+pub mod generator;
+#[cfg(feature = "serde")]
+pub mod json;
+pub mod parse_error;
 pub mod presents;
+pub mod puzzle;
+pub mod region;
+pub mod report;
+pub mod rng;
+pub mod solver;
 pub mod space;
+pub mod stream;
 pub mod tree;
 
 pub fn solve_pt1(input_file: &str) -> u64 {
-    let mut sections: Vec<&str> = input_file.split("\n\n").collect();
-
-    let tree_descriptions = sections.pop().unwrap();
-
-    let mut presents: Vec<PresentPossibilities> = Vec::with_capacity(sections.len() - 1);
-    for section in sections {
-        presents.push(PresentPossibilities::new(&section[3..]));
-    }
-
-    let mut trees: Vec<Tree> = Vec::with_capacity(tree_descriptions.lines().count());
-    for tree_description in tree_descriptions.lines() {
-        trees.push(Tree::new(tree_description, &presents));
-    }
-
-    let mut counter = 0;
-    for tree in trees {
-        if tree.simple_check() {
-            println!("Tree PASS");
-            counter += 1;
-        } else {
-            println!("Tree FAIL");
-        }
-    }
+    report_pt1(input_file).passed() as u64
+}
 
-    counter
+// One entry per region, each decided by the area check. Only the regions
+// it fails are sure; those it passes are a guess.
+pub fn report_pt1(input_file: &str) -> Report {
+    let mut report = Report::default();
+    solve_stream(input_file.as_bytes(), &SolveConfig::default(), |region| {
+        report.regions.push(region);
+        Ok(())
+    })
+    .unwrap();
+    report
 }
 
 pub fn solve_pt2(input_file: &str) -> u64 {
@@ -44,6 +48,8 @@ pub fn solve(input_file: &str) -> (u64, u64) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::solver::Registry;
+    use std::time::Duration;
 
     const EXAMPLE_PT1: u64 = 2;
     const EXAMPLE_PT2: u64 = 0;
@@ -73,6 +79,42 @@ mod tests {
     //     assert_eq!(part_2, ACTUAL_PT2);
     // }
     //
+    #[test]
+    fn example_report() {
+        let my_file = FileParser::new("data/example.txt");
+        let report = report_pt1(my_file.get_str());
+
+        assert_eq!(report.regions.len(), 3);
+        assert_eq!(report.regions[1].width, 12);
+        assert_eq!(report.regions[1].decided_by, "area");
+        assert_eq!(report.unproven(), report.passed());
+        assert_eq!(solve_pt1(my_file.get_str()), report.passed() as u64);
+    }
+
+    // Only a search shows which regions really fit. Showing the third
+    // doesn't takes far longer than finding packings for the other two, so
+    // it is left to time out, and doesn't count.
+    #[test]
+    fn example_answer() {
+        let my_file = FileParser::new("data/example.txt");
+        let config = SolveConfig {
+            solver: Registry::default().build("area,pack").unwrap(),
+            timeout: Some(Duration::from_secs(1)),
+            ..SolveConfig::default()
+        };
+        let mut report = Report::default();
+
+        solve_stream(my_file.get_str().as_bytes(), &config, |region| {
+            report.regions.push(region);
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(report.passed() as u64, EXAMPLE_PT1);
+        assert_eq!(report.unproven(), 0);
+        assert!(!report.regions[2].fits());
+    }
+
     #[test]
     fn actual_pts() {
         let my_file = FileParser::new("data/input.txt");
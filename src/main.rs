@@ -1,8 +1,15 @@
-use aoc_2025_xx::{file_parser::FileParser, solve, solve_pt1, solve_pt2};
+use std::{env, io, process::ExitCode};
 
-fn main() {
-    let my_file = FileParser::new("data/input.txt");
-    let (part_1, part_2) = solve(my_file.get_str());
-    println!("Part 1: {}", solve_pt1(my_file.get_str()));
-    println!("Part 2: {}", solve_pt2(my_file.get_str()));
+use aoc_2025_xx::cli::Cli;
+
+fn main() -> ExitCode {
+    let result = Cli::parse(env::args().skip(1)).and_then(|cli| cli.run(&mut io::stdout().lock()));
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::from(err.exit_code())
+        }
+    }
 }
@@ -0,0 +1,28 @@
+use std::fmt;
+
+// Why some piece of puzzle text couldn't be read. The `new` constructors
+// panic with this message, the `try_new` ones hand it back.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    pub message: String,
+}
+
+impl ParseError {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+mod formatting {
+    use super::*;
+
+    impl fmt::Display for ParseError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "Parse error: {}", self.message)
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crate::space::Space;
+use crate::{parse_error::ParseError, space::Space};
 use std::fmt;
 
 type PresentGrid = [[Space; 3]; 3];
@@ -16,25 +16,52 @@ macro_rules! present_without_whitespace {
     };
 }
 
+pub mod polyominoes;
 mod possibilities;
-pub use possibilities::PresentPossibilities;
+mod shape_set;
+mod symmetry;
+pub use possibilities::{OrientationPolicy, PresentPossibilities};
+pub use shape_set::ShapeSet;
+pub use symmetry::{SymmetryGroup, Transform};
 
 #[derive(Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Present {
     pub spaces: [[Space; 3]; 3],
 }
 
 impl Present {
     fn new(input: &str) -> Self {
+        Self::try_new(input).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    fn try_new(input: &str) -> Result<Self, ParseError> {
         let mut spaces: PresentGrid = [[Space::Free; 3]; 3];
 
         for (line_num, line) in input.lines().enumerate() {
             for (char_num, char) in line.chars().enumerate() {
+                if line_num >= 3 || char_num >= 3 {
+                    return Err(ParseError::new(format!(
+                        "Present is bigger than 3x3:\n{input}"
+                    )));
+                }
+                if !matches!(char, '#' | '.') {
+                    return Err(ParseError::new(format!(
+                        "Unknown present character {char:?}"
+                    )));
+                }
                 spaces[line_num][char_num] = Space::new(char);
             }
         }
 
-        Self { spaces }
+        // Nothing to place, and no size to divide the region by
+        let present = Self { spaces };
+        if present.first_occupied().is_none() {
+            return Err(ParseError::new(format!(
+                "Present has no occupied cells:\n{input}"
+            )));
+        }
+        Ok(present)
     }
 
     fn rotate(&mut self) {
@@ -65,6 +92,18 @@ impl Present {
         self.spaces = rotated_clone.spaces;
     }
 
+    // (row, col) of the first occupied cell in reading order
+    pub fn first_occupied(&self) -> Option<(usize, usize)> {
+        for (row_idx, row) in self.spaces.iter().enumerate() {
+            for (col_idx, space) in row.iter().enumerate() {
+                if *space == Space::Occupied {
+                    return Some((row_idx, col_idx));
+                }
+            }
+        }
+        None
+    }
+
     fn determine_free_space(&self) -> usize {
         let mut free_space = 0;
         for row in self.spaces {
@@ -106,6 +145,8 @@ mod formatting {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::rng::Rng;
+    use std::collections::HashSet;
 
     // #[test]
     // fn printing() {
@@ -162,4 +203,77 @@ mod tests {
 
         assert_eq!(5, pres.determine_free_space());
     }
+
+    // Each of the nine cells occupied or not at random, drawn again until
+    // at least one is
+    fn random_shape(rng: &mut Rng) -> String {
+        loop {
+            let rows: Vec<String> = (0..3)
+                .map(|_| {
+                    (0..3)
+                        .map(|_| if rng.chance(1, 2) { '#' } else { '.' })
+                        .collect()
+                })
+                .collect();
+            let shape = rows.join("\n");
+            if shape.contains('#') {
+                return shape;
+            }
+        }
+    }
+
+    #[test]
+    fn rotate_and_flip_properties() {
+        let mut rng = Rng::new(49);
+        for _ in 0..500 {
+            let pres = Present::new(&random_shape(&mut rng));
+
+            let mut turned = pres.clone();
+            for _ in 0..4 {
+                turned.rotate();
+                assert_eq!(turned.determine_free_space(), pres.determine_free_space());
+            }
+            assert_eq!(turned, pres);
+
+            let mut flipped = pres.clone();
+            flipped.flip();
+            assert_eq!(flipped.determine_free_space(), pres.determine_free_space());
+            flipped.flip();
+            assert_eq!(flipped, pres);
+        }
+    }
+
+    #[test]
+    fn orientation_properties() {
+        let mut rng = Rng::new(50);
+        let policies = [
+            OrientationPolicy::Fixed,
+            OrientationPolicy::Rotations,
+            OrientationPolicy::Dihedral,
+        ];
+        for _ in 0..500 {
+            let shape = random_shape(&mut rng);
+            let policy = policies[rng.below(policies.len())];
+            let poss = PresentPossibilities::with_policy(&shape, policy);
+
+            assert!([1, 2, 4, 8].contains(&poss.possibilities.len()), "{shape}");
+            let distinct: HashSet<&Present> = poss.possibilities.iter().collect();
+            assert_eq!(distinct.len(), poss.possibilities.len(), "{shape}");
+            for possibility in &poss.possibilities {
+                assert_eq!(9 - possibility.determine_free_space(), poss.get_size());
+            }
+        }
+    }
+
+    #[test]
+    fn first_occupied() {
+        present_without_whitespace!(pres, "...", ".##", "#..");
+        let empty = Present {
+            spaces: [[Space::Free; 3]; 3],
+        };
+
+        assert_eq!(pres.first_occupied(), Some((1, 1)));
+        assert_eq!(empty.first_occupied(), None);
+        assert!(Present::try_new("...\n...\n...").is_err());
+    }
 }
@@ -0,0 +1,194 @@
+use std::collections::HashSet;
+
+use super::*;
+
+// Enumerates polyominoes with Redelmeier's algorithm. Fixed ones are all
+// distinct up to translation, `Rotations` merges one-sided ones that only
+// differ by a turn, and `Dihedral` merges free ones that differ by any
+// rotation or reflection. Only shapes that fit a `box_size` square are kept,
+// and a `Present` can hold at most 3x3.
+pub fn polyominoes(size: usize, freedom: OrientationPolicy, box_size: usize) -> Vec<Present> {
+    let box_size = box_size.min(3);
+    let mut fixed: Vec<Present> = Vec::new();
+    if size == 0 || size > box_size * box_size {
+        return fixed;
+    }
+
+    let mut cells: Vec<(isize, isize)> = Vec::with_capacity(size);
+    let mut seen: HashSet<(isize, isize)> = HashSet::from([(0, 0)]);
+    extend(
+        vec![(0, 0)],
+        &mut cells,
+        &mut seen,
+        size,
+        box_size as isize,
+        &mut fixed,
+    );
+
+    let transforms: Vec<Transform> = Transform::all()
+        .into_iter()
+        .filter(|transform| match freedom {
+            OrientationPolicy::Fixed => *transform == Transform::IDENTITY,
+            OrientationPolicy::Rotations => transform.is_rotation(),
+            OrientationPolicy::Dihedral => true,
+        })
+        .collect();
+
+    let mut representatives: Vec<Present> = fixed
+        .iter()
+        .map(|present| {
+            transforms
+                .iter()
+                .map(|&transform| present.transformed(transform).normalised())
+                .min_by_key(|present| present.to_string())
+                .unwrap()
+        })
+        .collect::<HashSet<Present>>()
+        .into_iter()
+        .collect();
+    representatives.sort_by_key(|present| present.to_string());
+    representatives
+}
+
+pub fn polyominoes_up_to(
+    max_size: usize,
+    freedom: OrientationPolicy,
+    box_size: usize,
+) -> Vec<Present> {
+    (1..=max_size)
+        .flat_map(|size| polyominoes(size, freedom, box_size))
+        .collect()
+}
+
+// Ready to hand to `Tree`, every shape allowed the orientations `policy` gives
+pub fn shape_set(presents: &[Present], policy: OrientationPolicy) -> Vec<PresentPossibilities> {
+    presents
+        .iter()
+        .map(|present| PresentPossibilities::with_policy(&present.to_string(), policy))
+        .collect()
+}
+
+// Cells are only ever added above the origin row, or right of the origin on
+// it, so each fixed polyomino is built exactly once from its lowest-left cell
+fn extend(
+    mut untried: Vec<(isize, isize)>,
+    cells: &mut Vec<(isize, isize)>,
+    seen: &mut HashSet<(isize, isize)>,
+    size: usize,
+    box_size: isize,
+    found: &mut Vec<Present>,
+) {
+    while let Some(cell) = untried.pop() {
+        cells.push(cell);
+        if !fits_box(cells, box_size) {
+            cells.pop();
+            continue;
+        }
+
+        if cells.len() == size {
+            found.push(to_present(cells));
+        } else {
+            let mut new_untried = untried.clone();
+            let mut added: Vec<(isize, isize)> = Vec::new();
+            for neighbour in [
+                (cell.0 + 1, cell.1),
+                (cell.0 - 1, cell.1),
+                (cell.0, cell.1 + 1),
+                (cell.0, cell.1 - 1),
+            ] {
+                let allowed = neighbour.1 > 0 || (neighbour.1 == 0 && neighbour.0 >= 0);
+                if allowed && seen.insert(neighbour) {
+                    new_untried.push(neighbour);
+                    added.push(neighbour);
+                }
+            }
+            extend(new_untried, cells, seen, size, box_size, found);
+            for neighbour in added {
+                seen.remove(&neighbour);
+            }
+        }
+        cells.pop();
+    }
+}
+
+fn fits_box(cells: &[(isize, isize)], box_size: isize) -> bool {
+    let (min_x, max_x) = (
+        cells.iter().map(|c| c.0).min().unwrap(),
+        cells.iter().map(|c| c.0).max().unwrap(),
+    );
+    let (min_y, max_y) = (
+        cells.iter().map(|c| c.1).min().unwrap(),
+        cells.iter().map(|c| c.1).max().unwrap(),
+    );
+    max_x - min_x < box_size && max_y - min_y < box_size
+}
+
+fn to_present(cells: &[(isize, isize)]) -> Present {
+    let min_x = cells.iter().map(|c| c.0).min().unwrap();
+    let min_y = cells.iter().map(|c| c.1).min().unwrap();
+
+    let mut spaces: PresentGrid = [[Space::Free; 3]; 3];
+    for &(x, y) in cells {
+        spaces[(y - min_y) as usize][(x - min_x) as usize] = Space::Occupied;
+    }
+    Present { spaces }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_counts() {
+        // OEIS A001168, less the straight tetromino's two orientations for n=4
+        let counts: Vec<usize> = (1..=4)
+            .map(|size| polyominoes(size, OrientationPolicy::Fixed, 3).len())
+            .collect();
+        assert_eq!(counts, vec![1, 2, 6, 17]);
+
+        // A 3x3 square with any one cell missing
+        assert_eq!(polyominoes(8, OrientationPolicy::Fixed, 3).len(), 9);
+    }
+
+    #[test]
+    fn free_counts() {
+        // Without the pentominoes I, L, N and Y, which are longer than 3
+        let counts: Vec<usize> = (1..=5)
+            .map(|size| polyominoes(size, OrientationPolicy::Dihedral, 3).len())
+            .collect();
+        assert_eq!(counts, vec![1, 1, 2, 4, 8]);
+        assert_eq!(polyominoes(9, OrientationPolicy::Dihedral, 3).len(), 1);
+    }
+
+    #[test]
+    fn one_sided_counts() {
+        let counts: Vec<usize> = (4..=5)
+            .map(|size| polyominoes(size, OrientationPolicy::Rotations, 3).len())
+            .collect();
+        assert_eq!(counts, vec![6, 11]);
+    }
+
+    #[test]
+    fn box_filter() {
+        // Only the domino and the two trominoes that fit 2x2
+        assert_eq!(
+            polyominoes_up_to(3, OrientationPolicy::Dihedral, 2).len(),
+            3
+        );
+        assert!(polyominoes(5, OrientationPolicy::Dihedral, 2).is_empty());
+        assert_eq!(polyominoes(4, OrientationPolicy::Fixed, 2).len(), 1);
+    }
+
+    #[test]
+    fn distinct_and_normalised() {
+        let free = polyominoes(4, OrientationPolicy::Dihedral, 3);
+        let shapes = shape_set(&free, OrientationPolicy::Dihedral);
+
+        for present in &free {
+            assert_eq!(*present, present.normalised());
+            assert_eq!(present.determine_free_space(), 5);
+        }
+        let canonical: HashSet<Present> = shapes.iter().map(|s| s.canonical_form()).collect();
+        assert_eq!(canonical.len(), free.len());
+    }
+}
@@ -2,40 +2,172 @@ use std::collections::{HashMap, HashSet};
 
 use super::*;
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum OrientationPolicy {
+    // "This side up, this way round"
+    Fixed,
+    // Can be turned, but not flipped over
+    Rotations,
+    #[default]
+    Dihedral,
+}
+
+impl OrientationPolicy {
+    pub fn new(input: &str) -> Self {
+        Self::try_new(input).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_new(input: &str) -> Result<Self, ParseError> {
+        match input.trim() {
+            "fixed" => Ok(Self::Fixed),
+            "rotations" => Ok(Self::Rotations),
+            "dihedral" => Ok(Self::Dihedral),
+            other => Err(ParseError::new(format!(
+                "Unknown orientation policy {other:?}"
+            ))),
+        }
+    }
+}
+
+// Serialised as just the shape and its policy, since the orientations follow
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "ShapeFile", into = "ShapeFile")
+)]
 pub struct PresentPossibilities {
     pub possibilities: Vec<Present>,
     pub free_space: usize,
+    pub policy: OrientationPolicy,
 }
 
 impl PresentPossibilities {
     pub fn new(input: &str) -> Self {
-        let mut possibility = Present::new(input);
+        Self::with_policy(input, OrientationPolicy::default())
+    }
+
+    // Parses a whole shape section, "N:" or "N: <policy>" followed by the grid
+    pub fn from_section(section: &str, default_policy: OrientationPolicy) -> Self {
+        Self::try_from_section(section, default_policy).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_from_section(
+        section: &str,
+        default_policy: OrientationPolicy,
+    ) -> Result<Self, ParseError> {
+        let (header, grid) = section
+            .split_once('\n')
+            .ok_or_else(|| ParseError::new(format!("Shape {section:?} has no grid")))?;
+        let (_, policy) = header
+            .split_once(':')
+            .ok_or_else(|| ParseError::new(format!("Shape header {header:?} has no ':'")))?;
+        let policy = if policy.trim().is_empty() {
+            default_policy
+        } else {
+            OrientationPolicy::try_new(policy)?
+        };
+        Self::try_with_policy(grid, policy)
+    }
+
+    pub fn with_policy(input: &str, policy: OrientationPolicy) -> Self {
+        Self::try_with_policy(input, policy).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_with_policy(input: &str, policy: OrientationPolicy) -> Result<Self, ParseError> {
+        let mut possibility = Present::try_new(input)?;
         let free_space = possibility.determine_free_space();
-        let mut hash_set: HashSet<Present> = HashSet::new();
+        let mut possibilities: Vec<Present> = Vec::with_capacity(8);
+        let mut seen: HashSet<Present> = HashSet::new();
 
-        hash_set.insert(possibility.clone());
+        let (flips, rotations) = match policy {
+            OrientationPolicy::Fixed => (1, 1),
+            OrientationPolicy::Rotations => (1, 4),
+            OrientationPolicy::Dihedral => (2, 4),
+        };
 
-        for _ in 0..3 {
-            possibility.rotate();
-            hash_set.insert(possibility.clone());
-        }
-        possibility.flip();
-        hash_set.insert(possibility.clone());
-        for _ in 0..3 {
-            possibility.rotate();
-            hash_set.insert(possibility.clone());
+        // Kept in generation order so orientation indices are stable between runs
+        for flipped in [false, true].into_iter().take(flips) {
+            if flipped {
+                possibility.flip();
+            }
+            for _ in 0..rotations {
+                if seen.insert(possibility.clone()) {
+                    possibilities.push(possibility.clone());
+                }
+                possibility.rotate();
+            }
         }
 
-        Self {
-            possibilities: hash_set.iter().cloned().collect(),
+        Ok(Self {
+            possibilities,
             free_space,
-        }
+            policy,
+        })
     }
 
     pub fn get_size(&self) -> usize {
         9 - self.free_space
     }
+
+    // Index of the orientation mirrored across the main diagonal
+    pub fn transposed(&self, poss_idx: usize) -> Option<usize> {
+        let mut transposed = self.possibilities[poss_idx].clone();
+        transposed.rotate();
+        transposed.flip();
+        self.possibilities.iter().position(|p| *p == transposed)
+    }
+
+    // Whether a region can be swapped across its diagonal without changing
+    // which placements this present has
+    pub fn is_transpose_closed(&self) -> bool {
+        (0..self.possibilities.len()).all(|idx| self.transposed(idx).is_some())
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ShapeFile {
+    shape: Present,
+    policy: OrientationPolicy,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<ShapeFile> for PresentPossibilities {
+    type Error = ParseError;
+
+    fn try_from(file: ShapeFile) -> Result<Self, ParseError> {
+        Self::try_with_policy(&file.shape.to_string(), file.policy)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<PresentPossibilities> for ShapeFile {
+    fn from(shape: PresentPossibilities) -> Self {
+        Self {
+            shape: shape.possibilities[0].clone(),
+            policy: shape.policy,
+        }
+    }
+}
+
+mod formatting {
+    use super::*;
+
+    impl fmt::Display for OrientationPolicy {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Self::Fixed => write!(f, "fixed"),
+                Self::Rotations => write!(f, "rotations"),
+                Self::Dihedral => write!(f, "dihedral"),
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -132,4 +264,120 @@ mod tests {
         assert!(poss.possibilities.contains(&pres3));
         assert_eq!(poss.possibilities.len(), 4);
     }
+
+    #[test]
+    fn find_possibilities_policies() {
+        present_without_whitespace!(pres, "#..", "...", "...");
+        present_without_whitespace!(pres1, "..#", "...", "...");
+
+        let fixed = PresentPossibilities::with_policy("#..\n...\n...", OrientationPolicy::Fixed);
+        let rotations =
+            PresentPossibilities::with_policy("#..\n...\n...", OrientationPolicy::Rotations);
+
+        assert_eq!(fixed.possibilities, vec![pres.clone()]);
+        assert_eq!(fixed.free_space, 8);
+        assert_eq!(rotations.possibilities.len(), 4);
+        assert!(rotations.possibilities.contains(&pres));
+        assert!(rotations.possibilities.contains(&pres1));
+    }
+
+    #[test]
+    fn find_possibilities_sym_policies() {
+        present_without_whitespace!(
+            pres,  //
+            "###", //
+            ".#.", //
+            ".#."  //
+        );
+        present_without_whitespace!(
+            pres1, //
+            "..#", //
+            "###", //
+            "..#"  //
+        );
+
+        let fixed = PresentPossibilities::with_policy("###\n.#.\n.#.", OrientationPolicy::Fixed);
+        let rotations =
+            PresentPossibilities::with_policy("###\n.#.\n.#.", OrientationPolicy::Rotations);
+
+        assert_eq!(fixed.possibilities, vec![pres.clone()]);
+        // Mirror symmetric, so reflections add nothing
+        assert_eq!(rotations.possibilities.len(), 4);
+        assert!(rotations.possibilities.contains(&pres));
+        assert!(rotations.possibilities.contains(&pres1));
+        assert!(!fixed.is_transpose_closed());
+    }
+
+    #[test]
+    fn find_possibilities_rot_sym_policies() {
+        present_without_whitespace!(
+            pres,  //
+            ".##", //
+            ".#.", //
+            "##."  //
+        );
+        present_without_whitespace!(
+            pres1, //
+            "#..", //
+            "###", //
+            "..#"  //
+        );
+        present_without_whitespace!(
+            mirrored, //
+            "##.",    //
+            ".#.",    //
+            ".##"     //
+        );
+
+        let fixed = PresentPossibilities::with_policy(".##\n.#.\n##.", OrientationPolicy::Fixed);
+        let rotations =
+            PresentPossibilities::with_policy(".##\n.#.\n##.", OrientationPolicy::Rotations);
+
+        assert_eq!(fixed.possibilities, vec![pres.clone()]);
+        // Half-turn symmetric, so only two distinct rotations
+        assert_eq!(rotations.possibilities, vec![pres, pres1]);
+        assert!(!rotations.possibilities.contains(&mirrored));
+        assert!(!rotations.is_transpose_closed());
+        assert!(PresentPossibilities::new(".##\n.#.\n##.").is_transpose_closed());
+    }
+
+    #[test]
+    fn from_section() {
+        let default =
+            PresentPossibilities::from_section("0:\n##.\n#..\n...", OrientationPolicy::Fixed);
+        let marked = PresentPossibilities::from_section(
+            "12: rotations\n##.\n#..\n...",
+            OrientationPolicy::Fixed,
+        );
+
+        assert_eq!(default.policy, OrientationPolicy::Fixed);
+        assert_eq!(default.possibilities.len(), 1);
+        assert_eq!(marked.policy, OrientationPolicy::Rotations);
+        assert_eq!(marked.possibilities.len(), 4);
+    }
+
+    #[test]
+    fn transposed() {
+        present_without_whitespace!(pres, "##.", "...", "...");
+        present_without_whitespace!(pres1, "#..", "#..", "...");
+
+        let poss = PresentPossibilities::new("##.\n...\n...");
+        let idx = poss.possibilities.iter().position(|p| *p == pres).unwrap();
+        let idx1 = poss.possibilities.iter().position(|p| *p == pres1).unwrap();
+
+        assert_eq!(poss.transposed(idx), Some(idx1));
+        assert_eq!(poss.transposed(idx1), Some(idx));
+    }
+
+    #[test]
+    fn stable_order() {
+        present_without_whitespace!(pres, "##.", "#..", "...");
+        present_without_whitespace!(pres1, ".##", "..#", "...");
+
+        let poss = PresentPossibilities::new("##.\n#..\n...");
+
+        assert_eq!(poss.possibilities[0], pres);
+        assert_eq!(poss.possibilities[1], pres1);
+        assert_eq!(poss.possibilities.len(), 4);
+    }
 }
@@ -0,0 +1,155 @@
+use std::{
+    ops::Deref,
+    sync::{Arc, Mutex},
+};
+
+use super::*;
+use crate::{fnv::fnv1a, tree::PlacementTable};
+
+// How many grid sizes keep their table. Real inputs repeat a few sizes, and
+// generated ones can have any number of them, so the least recently used
+// goes once there are more.
+const TABLES_KEPT: usize = 16;
+
+// Least recently used first
+type Tables = Vec<((usize, usize), Arc<PlacementTable>)>;
+
+// A puzzle's shapes, shared by every region built from them. Clones share the
+// same allocation, so trees can own one and still be sent between threads.
+#[derive(Clone, Default)]
+pub struct ShapeSet {
+    shapes: Arc<[PresentPossibilities]>,
+    // Built on first use, by grid rows and columns
+    tables: Arc<Mutex<Tables>>,
+}
+
+impl ShapeSet {
+    pub fn new(shapes: Vec<PresentPossibilities>) -> Self {
+        Self {
+            shapes: shapes.into(),
+            tables: Arc::default(),
+        }
+    }
+
+    // The same table for every region whose grid has this size, as long as
+    // the size is among the most recently used
+    pub fn placement_table(&self, rows: usize, cols: usize) -> Arc<PlacementTable> {
+        let mut tables = self.tables.lock().unwrap();
+        let entry = match tables.iter().position(|(size, _)| *size == (rows, cols)) {
+            Some(idx) => tables.remove(idx),
+            None => {
+                if tables.len() == TABLES_KEPT {
+                    tables.remove(0);
+                }
+                let table = Arc::new(PlacementTable::new(&self.shapes, rows, cols));
+                ((rows, cols), table)
+            }
+        };
+        let table = entry.1.clone();
+        tables.push(entry);
+        table
+    }
+
+    // FNV-1a over every orientation of every shape, in order, so it's the
+    // same from one run, or build, to the next
+    pub fn fingerprint(&self) -> u64 {
+        fnv1a(self.shapes.iter().flat_map(|shape| {
+            (shape.possibilities.iter())
+                .flat_map(|possibility| {
+                    possibility
+                        .to_string()
+                        .into_bytes()
+                        .into_iter()
+                        .chain([b';'])
+                })
+                .chain([b'|'])
+        }))
+    }
+
+    // Whether both were cloned from the same set, rather than just being equal
+    pub fn shares(&self, other: &ShapeSet) -> bool {
+        Arc::ptr_eq(&self.shapes, &other.shapes)
+    }
+}
+
+impl Deref for ShapeSet {
+    type Target = [PresentPossibilities];
+
+    fn deref(&self) -> &Self::Target {
+        &self.shapes
+    }
+}
+
+impl PartialEq for ShapeSet {
+    fn eq(&self, other: &Self) -> bool {
+        self.shapes == other.shapes
+    }
+}
+
+impl Eq for ShapeSet {}
+
+impl From<Vec<PresentPossibilities>> for ShapeSet {
+    fn from(shapes: Vec<PresentPossibilities>) -> Self {
+        Self::new(shapes)
+    }
+}
+
+// Copies the shapes; convert once and clone the set to share them
+impl From<&Vec<PresentPossibilities>> for ShapeSet {
+    fn from(shapes: &Vec<PresentPossibilities>) -> Self {
+        Self::new(shapes.clone())
+    }
+}
+
+impl From<&ShapeSet> for ShapeSet {
+    fn from(shapes: &ShapeSet) -> Self {
+        shapes.clone()
+    }
+}
+
+mod formatting {
+    use super::*;
+
+    impl fmt::Debug for ShapeSet {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_list().entries(self.shapes.iter()).finish()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sharing() {
+        let shapes = vec![PresentPossibilities::new("###\n#..\n###")];
+        let set = ShapeSet::from(&shapes);
+        let clone = ShapeSet::from(&set);
+
+        assert!(set.shares(&clone));
+        assert_eq!(set.fingerprint(), ShapeSet::from(&shapes).fingerprint());
+        assert_ne!(set.fingerprint(), ShapeSet::default().fingerprint());
+        assert!(!set.shares(&ShapeSet::from(&shapes)));
+        assert_eq!(set, ShapeSet::from(&shapes));
+        assert_eq!(clone.len(), 1);
+        assert_eq!(clone[0].get_size(), 7);
+    }
+
+    #[test]
+    fn tables_are_bounded() {
+        let set = ShapeSet::from(&vec![PresentPossibilities::new("###\n#..\n###")]);
+        let first = set.placement_table(5, 5);
+        let second = set.placement_table(5, 6);
+        for cols in 7..5 + TABLES_KEPT {
+            set.placement_table(5, cols);
+        }
+
+        // Used again, so the next size pushes out the one after it instead
+        assert!(Arc::ptr_eq(&first, &set.placement_table(5, 5)));
+        set.placement_table(6, 6);
+        assert!(Arc::ptr_eq(&first, &set.placement_table(5, 5)));
+        assert!(!Arc::ptr_eq(&second, &set.placement_table(5, 6)));
+        assert_eq!(set.tables.lock().unwrap().len(), TABLES_KEPT);
+    }
+}
@@ -0,0 +1,251 @@
+use std::fmt;
+
+use super::*;
+
+// One of the 8 symmetries of the square: flip left-right first if
+// `flipped`, then turn clockwise `rotations` quarter turns. The orientations
+// in `PresentPossibilities` are generated in this same order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Transform {
+    pub flipped: bool,
+    pub rotations: u8,
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self {
+        flipped: false,
+        rotations: 0,
+    };
+
+    pub fn all() -> [Self; 8] {
+        let mut transforms = [Self::IDENTITY; 8];
+        for (idx, transform) in transforms.iter_mut().enumerate() {
+            transform.flipped = idx >= 4;
+            transform.rotations = (idx % 4) as u8;
+        }
+        transforms
+    }
+
+    pub fn index(&self) -> usize {
+        self.flipped as usize * 4 + self.rotations as usize
+    }
+
+    pub fn is_rotation(&self) -> bool {
+        !self.flipped
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SymmetryGroup {
+    // Cyclic groups: only rotations
+    C1,
+    C2,
+    C4,
+    // Dihedral groups: rotations plus 1, 2 or 4 mirror lines
+    D1,
+    D2,
+    D4,
+}
+
+impl SymmetryGroup {
+    pub fn from_symmetries(symmetries: &[Transform]) -> Self {
+        let half_turn = Transform {
+            flipped: false,
+            rotations: 2,
+        };
+        let quarter_turn = Transform {
+            flipped: false,
+            rotations: 1,
+        };
+        match symmetries.len() {
+            1 => Self::C1,
+            2 if symmetries.contains(&half_turn) => Self::C2,
+            2 => Self::D1,
+            4 if symmetries.contains(&quarter_turn) => Self::C4,
+            4 => Self::D2,
+            8 => Self::D4,
+            _ => panic!("{} symmetries is not a subgroup of D4", symmetries.len()),
+        }
+    }
+
+    pub fn order(&self) -> usize {
+        match self {
+            Self::C1 => 1,
+            Self::C2 | Self::D1 => 2,
+            Self::C4 | Self::D2 => 4,
+            Self::D4 => 8,
+        }
+    }
+}
+
+impl Present {
+    pub fn transformed(&self, transform: Transform) -> Self {
+        let mut present = self.clone();
+        if transform.flipped {
+            present.flip();
+        }
+        for _ in 0..transform.rotations {
+            present.rotate();
+        }
+        present
+    }
+
+    // Slid up and left as far as it goes, so shapes compare regardless of
+    // where they sit in the 3x3 box
+    pub fn normalised(&self) -> Self {
+        if self.first_occupied().is_none() {
+            return self.clone();
+        }
+        let occupied = |row: usize, col: usize| self.spaces[row][col] == Space::Occupied;
+        let top = (0..3)
+            .find(|&row| (0..3).any(|col| occupied(row, col)))
+            .unwrap();
+        let left = (0..3)
+            .find(|&col| (0..3).any(|row| occupied(row, col)))
+            .unwrap();
+
+        let mut spaces: PresentGrid = [[Space::Free; 3]; 3];
+        for row in top..3 {
+            for col in left..3 {
+                spaces[row - top][col - left] = self.spaces[row][col];
+            }
+        }
+        Self { spaces }
+    }
+}
+
+impl PresentPossibilities {
+    // Transforms that leave the shape unchanged, ignoring its place in the box
+    pub fn symmetries(&self) -> Vec<Transform> {
+        let shape = self.possibilities[0].normalised();
+        Transform::all()
+            .into_iter()
+            .filter(|&transform| self.possibilities[0].transformed(transform).normalised() == shape)
+            .collect()
+    }
+
+    pub fn symmetry_group(&self) -> SymmetryGroup {
+        SymmetryGroup::from_symmetries(&self.symmetries())
+    }
+
+    // Orientation index reached by each transform of the shape as parsed,
+    // indexed by `Transform::index`. `None` where the policy forbids it.
+    pub fn transform_map(&self) -> [Option<usize>; 8] {
+        let mut map = [None; 8];
+        for transform in Transform::all() {
+            let image = self.possibilities[0].transformed(transform);
+            map[transform.index()] = self.possibilities.iter().position(|p| *p == image);
+        }
+        map
+    }
+
+    // The same shape for every input that differs only by a rotation,
+    // reflection or shift within the box
+    pub fn canonical_form(&self) -> Present {
+        Transform::all()
+            .into_iter()
+            .map(|transform| self.possibilities[0].transformed(transform).normalised())
+            .min_by_key(|present| present.to_string())
+            .unwrap()
+    }
+}
+
+mod formatting {
+    use super::*;
+
+    impl fmt::Display for SymmetryGroup {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let name = match self {
+                Self::C1 => "C1",
+                Self::C2 => "C2",
+                Self::C4 => "C4",
+                Self::D1 => "D1",
+                Self::D2 => "D2",
+                Self::D4 => "D4",
+            };
+            write!(f, "{name}")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transform_order() {
+        let transforms = Transform::all();
+
+        for (idx, transform) in transforms.iter().enumerate() {
+            assert_eq!(transform.index(), idx);
+        }
+        assert_eq!(transforms[0], Transform::IDENTITY);
+        assert!(transforms[3].is_rotation());
+        assert!(!transforms[4].is_rotation());
+    }
+
+    #[test]
+    fn groups() {
+        for (shape, group) in [
+            ("#..\n...\n...", SymmetryGroup::D4),
+            ("###\n#.#\n###", SymmetryGroup::D4),
+            ("##.\n...\n...", SymmetryGroup::D2),
+            ("###\n.#.\n.#.", SymmetryGroup::D1),
+            ("##.\n#..\n...", SymmetryGroup::D1),
+            (".##\n.#.\n##.", SymmetryGroup::C2),
+            (".##\n##.\n...", SymmetryGroup::C2),
+            ("###\n##.\n##.", SymmetryGroup::C1),
+        ] {
+            let poss = PresentPossibilities::new(shape);
+            assert_eq!(poss.symmetry_group(), group, "{shape}");
+            assert_eq!(poss.symmetries().len(), group.order());
+        }
+    }
+
+    #[test]
+    fn group_ignores_policy() {
+        let poss = PresentPossibilities::with_policy(".##\n.#.\n##.", OrientationPolicy::Fixed);
+
+        assert_eq!(poss.symmetry_group(), SymmetryGroup::C2);
+    }
+
+    #[test]
+    fn transform_map() {
+        let poss = PresentPossibilities::new(".##\n.#.\n##.");
+        let map = poss.transform_map();
+
+        // Half-turn symmetric, so the transforms pair up
+        assert_eq!(map[0], Some(0));
+        assert_eq!(map[1], Some(1));
+        assert_eq!(map[2], Some(0));
+        assert_eq!(map[3], Some(1));
+        assert_eq!(map[4], map[6]);
+        assert_eq!(map[5], map[7]);
+        for transform in Transform::all() {
+            let idx = map[transform.index()].unwrap();
+            assert_eq!(
+                poss.possibilities[idx],
+                poss.possibilities[0].transformed(transform)
+            );
+        }
+
+        let rotations =
+            PresentPossibilities::with_policy(".##\n.#.\n##.", OrientationPolicy::Rotations);
+        assert_eq!(rotations.transform_map()[4], None);
+    }
+
+    #[test]
+    fn canonical_form() {
+        let poss = PresentPossibilities::new("...\n.##\n.#.");
+        let rotated = PresentPossibilities::new("...\n##.\n.#.");
+        let shifted = PresentPossibilities::new("##.\n#..\n...");
+
+        assert_eq!(poss.canonical_form(), rotated.canonical_form());
+        assert_eq!(poss.canonical_form(), shifted.canonical_form());
+        assert_eq!(poss.canonical_form(), poss.canonical_form().normalised());
+        assert_ne!(
+            poss.canonical_form(),
+            PresentPossibilities::new("###\n...\n...").canonical_form()
+        );
+    }
+}
@@ -0,0 +1,212 @@
+use std::{fmt, io};
+
+use crate::{
+    parse_error::ParseError,
+    presents::{OrientationPolicy, PresentPossibilities, ShapeSet},
+    region::RegionSpec,
+    stream::RegionStream,
+    tree::Tree,
+};
+
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "PuzzleFile")
+)]
+pub struct Puzzle {
+    pub shapes: Vec<PresentPossibilities>,
+    pub regions: Vec<RegionSpec>,
+}
+
+impl Puzzle {
+    pub fn new(input: &str) -> Self {
+        Self::try_new(input).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    // Read the way `solve` streams a file, so the two agree on every input
+    pub fn try_new(input: &str) -> Result<Self, ParseError> {
+        let stream = RegionStream::new(input.as_bytes()).map_err(parse_error)?;
+        let shapes = stream.shapes.clone();
+        let regions = stream
+            .collect::<io::Result<Vec<RegionSpec>>>()
+            .map_err(parse_error)?;
+        Ok(Self { shapes, regions })
+    }
+
+    pub fn trees(&self) -> Vec<Tree> {
+        let shapes = ShapeSet::from(&self.shapes);
+        self.regions
+            .iter()
+            .map(|region| Tree::from_spec(region, &shapes))
+            .collect()
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct PuzzleFile {
+    shapes: Vec<PresentPossibilities>,
+    regions: Vec<RegionSpec>,
+}
+
+// The text and binary readers hold regions to the shapes as they go, so
+// json has to as well
+#[cfg(feature = "serde")]
+impl TryFrom<PuzzleFile> for Puzzle {
+    type Error = ParseError;
+
+    fn try_from(file: PuzzleFile) -> Result<Self, ParseError> {
+        for region in &file.regions {
+            check_demand(region, file.shapes.len())?;
+        }
+        Ok(Self {
+            shapes: file.shapes,
+            regions: file.regions,
+        })
+    }
+}
+
+// Every region needs one count per shape
+pub fn check_demand(region: &RegionSpec, shape_count: usize) -> Result<(), ParseError> {
+    if region.demand.len() != shape_count {
+        return Err(ParseError::new(format!(
+            "Region \"{region}\" has {} counts for {shape_count} shapes",
+            region.demand.len()
+        )));
+    }
+    Ok(())
+}
+
+// Reading from a string only goes wrong where the text does
+fn parse_error(err: io::Error) -> ParseError {
+    match err.into_inner().map(|inner| inner.downcast::<ParseError>()) {
+        Some(Ok(err)) => *err,
+        Some(Err(inner)) => ParseError::new(inner.to_string()),
+        None => ParseError::new("Unreadable puzzle text"),
+    }
+}
+
+mod formatting {
+    use super::*;
+
+    // Canonical form: every shape gets its own header, with the policy only
+    // written when it isn't the default, and regions are one line each
+    impl fmt::Display for Puzzle {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            for (idx, shape) in self.shapes.iter().enumerate() {
+                if shape.policy == OrientationPolicy::default() {
+                    writeln!(f, "{idx}:")?;
+                } else {
+                    writeln!(f, "{idx}: {}", shape.policy)?;
+                }
+                writeln!(f, "{}", shape.possibilities[0])?;
+            }
+            for region in &self.regions {
+                writeln!(f, "{region}")?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        file_parser::FileParser,
+        generator::{GeneratorConfig, generate},
+    };
+
+    fn round_trip(input: &str) -> Puzzle {
+        let puzzle = Puzzle::new(input);
+        let written = puzzle.to_string();
+        let reparsed = Puzzle::new(&written);
+
+        assert_eq!(puzzle, reparsed);
+        assert_eq!(written, reparsed.to_string());
+        puzzle
+    }
+
+    #[test]
+    fn example() {
+        let my_file = FileParser::new("data/example.txt");
+        let puzzle = round_trip(my_file.get_str());
+
+        assert_eq!(puzzle.shapes.len(), 6);
+        assert_eq!(puzzle.regions.len(), 3);
+        assert_eq!(puzzle.to_string().trim_end(), my_file.get_str().trim_end());
+    }
+
+    #[test]
+    fn extended_format() {
+        let input = "orientations: rotations\n\n\
+                     0:\n###\n#..\n###\n\n\
+                     1: dihedral\n##.\n...\n...\n\n\
+                     4x4: 0 2\n\
+                     5x3: 1 1 | pinned 0:1@0,0\n\
+                     4x3: 1 0\n#...\n....\n...#";
+        let puzzle = round_trip(input);
+
+        assert_eq!(puzzle.shapes[0].policy, OrientationPolicy::Rotations);
+        assert_eq!(puzzle.shapes[1].policy, OrientationPolicy::Dihedral);
+        assert_eq!(puzzle.regions[1].pinned.len(), 1);
+        assert_eq!(puzzle.regions[2].blocked, vec![(0, 0), (3, 2)]);
+
+        let written = puzzle.to_string();
+        assert!(written.starts_with("0: rotations\n###\n#..\n###\n\n1:\n"));
+        assert!(written.contains("\n4x3: 1 0 | blocked 0,0 3,2\n"));
+    }
+
+    #[test]
+    fn parse_errors() {
+        for input in [
+            "0: sideways\n###\n#..\n###\n\n4x4: 1",
+            "0:\n####\n#..\n###\n\n4x4: 1",
+            "0:\n#*#\n#..\n###\n\n4x4: 1",
+            "0:\n...\n...\n...\n\n3x3: 1",
+            "0:\n###\n#..\n###\n\n4x4: 1 2",
+            "0:\n###\n#..\n###\n\n4x4: 1\n5y5: 1",
+        ] {
+            assert!(Puzzle::try_new(input).is_err(), "{input}");
+        }
+
+        let err = Puzzle::try_new("0:\n###\n#..\n###\n\n4x4: 1 2").unwrap_err();
+        assert_eq!(err.message, "Region \"4x4: 1 2\" has 2 counts for 1 shapes");
+    }
+
+    // Blank lines between regions, as `solve` has always taken them
+    #[test]
+    fn blank_lines() {
+        let spaced = "0:\n###\n#..\n###\n\n4x4: 1\n\n5x4: 1\n\n\n4x3: 0\n#...\n\n....\n...#\n";
+        let tidy = "0:\n###\n#..\n###\n\n4x4: 1\n5x4: 1\n4x3: 0\n#...\n....\n...#\n";
+
+        let puzzle = Puzzle::new(spaced);
+
+        assert_eq!(puzzle, Puzzle::new(tidy));
+        assert_eq!(puzzle.shapes.len(), 1);
+        assert_eq!(puzzle.regions.len(), 3);
+        assert_eq!(puzzle.regions[2].blocked, vec![(0, 0), (3, 2)]);
+    }
+
+    #[test]
+    fn generated() {
+        let shapes = vec![
+            PresentPossibilities::new("###\n##.\n##."),
+            PresentPossibilities::with_policy("###\n.#.\n###", OrientationPolicy::Fixed),
+        ];
+        let config = GeneratorConfig {
+            seed: 1,
+            regions: 10,
+            widths: 3..=9,
+            heights: 3..=9,
+            unsolvable_percent: 50,
+        };
+        let text = generate(&shapes, &config).text;
+
+        let puzzle = round_trip(&text);
+
+        assert_eq!(puzzle.shapes, shapes);
+        assert_eq!(puzzle.to_string(), text);
+    }
+}
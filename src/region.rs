@@ -0,0 +1,405 @@
+use std::{fmt, str::FromStr};
+
+use crate::parse_error::ParseError;
+
+// A region as written in the input, before it is turned into a `Tree`.
+//
+// Three forms are accepted:
+//   12x5: 1 0 1 0 2 2
+//   12x5: 1 0 1 0 2 2 | blocked 0,0 11,4
+//   4x3: 0 0 1
+//   #...
+//   ....
+//   ...#
+// Blocked cells are (x, y) with x across the width and y down the height.
+// Any form can also pin presents in place before the search starts:
+//   12x5: 1 0 1 0 2 2 | pinned 4:0@0,0 5:1@3,2
+// which reads present:orientation@x,y, with x,y the top left of the 3x3 box.
+// That can be -1 when the orientation's first column or row is empty, so the
+// box hangs over the left or top edge.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "RegionFile")
+)]
+pub struct RegionSpec {
+    pub width: usize,
+    pub height: usize,
+    pub demand: Vec<usize>,
+    pub blocked: Vec<(usize, usize)>,
+    pub pinned: Vec<Pin>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Pin {
+    pub present_idx: usize,
+    pub poss_idx: usize,
+    pub x: isize,
+    pub y: isize,
+}
+
+// What a region read from outside the text format can't be built with
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SpecError {
+    BlockedOutside(usize, usize),
+    UnknownPresent(Pin),
+    PinOutside(Pin),
+}
+
+impl Pin {
+    pub fn new(input: &str) -> Self {
+        Self::try_new(input).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_new(input: &str) -> Result<Self, ParseError> {
+        let bad_pin = || ParseError::new(format!("Pin {input:?} isn't present:orientation@x,y"));
+        let (kind, position) = input.split_once('@').ok_or_else(bad_pin)?;
+        let (present_idx, poss_idx) = kind.split_once(':').ok_or_else(bad_pin)?;
+        let (x, y) = position.split_once(',').ok_or_else(bad_pin)?;
+
+        Ok(Self {
+            present_idx: parse_number(present_idx)?,
+            poss_idx: parse_number(poss_idx)?,
+            x: parse_number(x)?,
+            y: parse_number(y)?,
+        })
+    }
+}
+
+impl RegionSpec {
+    pub fn new(input: &str) -> Self {
+        Self::try_new(input).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_new(input: &str) -> Result<Self, ParseError> {
+        let mut lines = input.lines();
+        let header = lines
+            .next()
+            .ok_or_else(|| ParseError::new("Empty region description"))?;
+        let (size_string, rest) = header
+            .split_once(':')
+            .ok_or_else(|| ParseError::new(format!("Region header {header:?} has no ':'")))?;
+
+        let (width, height) = size_string.trim().split_once('x').ok_or_else(|| {
+            ParseError::new(format!("Region size {size_string:?} isn't WIDTHxHEIGHT"))
+        })?;
+        let width: usize = parse_number(width)?;
+        let height: usize = parse_number(height)?;
+
+        let mut clauses = rest.split('|');
+        let demand: Vec<usize> = clauses
+            .next()
+            .unwrap()
+            .split_whitespace()
+            .map(parse_number)
+            .collect::<Result<Vec<usize>, ParseError>>()?;
+
+        let mut blocked: Vec<(usize, usize)> = Vec::new();
+        let mut pinned: Vec<Pin> = Vec::new();
+        for clause in clauses {
+            let mut words = clause.split_whitespace();
+            match words.next() {
+                Some("blocked") => {
+                    for coord in words {
+                        let (x, y) = coord.split_once(',').ok_or_else(|| {
+                            ParseError::new(format!("Blocked cell {coord:?} isn't x,y"))
+                        })?;
+                        blocked.push((parse_number(x)?, parse_number(y)?));
+                    }
+                }
+                Some("pinned") => {
+                    for word in words {
+                        pinned.push(Pin::try_new(word)?);
+                    }
+                }
+                other => {
+                    return Err(ParseError::new(format!("Unknown region clause {other:?}")));
+                }
+            }
+        }
+
+        let mask_rows: Vec<&str> = lines.collect();
+        if !mask_rows.is_empty() {
+            if mask_rows.len() != height {
+                return Err(ParseError::new(format!(
+                    "Region mask for {header:?} has the wrong height"
+                )));
+            }
+            for (y, row) in mask_rows.iter().enumerate() {
+                if row.len() != width {
+                    return Err(ParseError::new(format!(
+                        "Region mask for {header:?} has the wrong width"
+                    )));
+                }
+                for (x, char) in row.chars().enumerate() {
+                    match char {
+                        '#' => blocked.push((x, y)),
+                        '.' => {}
+                        _ => {
+                            return Err(ParseError::new(format!(
+                                "Unknown region mask character {char:?}"
+                            )));
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some((x, y)) = blocked.iter().find(|&&(x, y)| x >= width || y >= height) {
+            return Err(ParseError::new(format!(
+                "Blocked cell {x},{y} is outside the region"
+            )));
+        }
+
+        let mut spec = Self::with_blocked(width, height, demand, blocked);
+        spec.pinned = pinned;
+        Ok(spec)
+    }
+
+    pub fn rectangle(width: usize, height: usize, demand: Vec<usize>) -> Self {
+        Self::with_blocked(width, height, demand, vec![])
+    }
+
+    pub fn with_blocked(
+        width: usize,
+        height: usize,
+        demand: Vec<usize>,
+        mut blocked: Vec<(usize, usize)>,
+    ) -> Self {
+        for &(x, y) in &blocked {
+            assert!(
+                x < width && y < height,
+                "Blocked cell {x},{y} is outside the region"
+            );
+        }
+        blocked.sort_by_key(|&(x, y)| (y, x));
+        blocked.dedup();
+
+        Self {
+            width,
+            height,
+            demand,
+            blocked,
+            pinned: vec![],
+        }
+    }
+
+    // Refuses what `with_blocked` would panic on, and pins that can't
+    // belong to the region, before anything is built from them
+    pub fn try_from_parts(
+        width: usize,
+        height: usize,
+        demand: Vec<usize>,
+        blocked: Vec<(usize, usize)>,
+        pinned: Vec<Pin>,
+    ) -> Result<Self, SpecError> {
+        if let Some(&(x, y)) = blocked.iter().find(|&&(x, y)| x >= width || y >= height) {
+            return Err(SpecError::BlockedOutside(x, y));
+        }
+        for &pin in &pinned {
+            if pin.present_idx >= demand.len() {
+                return Err(SpecError::UnknownPresent(pin));
+            }
+            if !pin_within(pin.x, width) || !pin_within(pin.y, height) {
+                return Err(SpecError::PinOutside(pin));
+            }
+        }
+        let mut spec = Self::with_blocked(width, height, demand, blocked);
+        spec.pinned = pinned;
+        Ok(spec)
+    }
+
+    pub fn free_cells(&self) -> usize {
+        self.width * self.height - self.blocked.len()
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct RegionFile {
+    width: usize,
+    height: usize,
+    demand: Vec<usize>,
+    blocked: Vec<(usize, usize)>,
+    pinned: Vec<Pin>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<RegionFile> for RegionSpec {
+    type Error = SpecError;
+
+    fn try_from(file: RegionFile) -> Result<Self, SpecError> {
+        Self::try_from_parts(
+            file.width,
+            file.height,
+            file.demand,
+            file.blocked,
+            file.pinned,
+        )
+    }
+}
+
+// A box can start one cell before the region, and has to start inside it
+fn pin_within(coord: isize, size: usize) -> bool {
+    coord >= -1 && (coord < 0 || (coord as usize) < size)
+}
+
+fn parse_number<T: FromStr>(input: &str) -> Result<T, ParseError> {
+    input
+        .trim()
+        .parse()
+        .map_err(|_| ParseError::new(format!("Expected a number, found {input:?}")))
+}
+
+mod formatting {
+    use super::*;
+
+    impl fmt::Display for SpecError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Self::BlockedOutside(x, y) => {
+                    write!(f, "Blocked cell {x},{y} is outside the region")
+                }
+                Self::UnknownPresent(pin) => write!(f, "Pin {pin} is of an unknown present"),
+                Self::PinOutside(pin) => write!(f, "Pin {pin} is outside the region"),
+            }
+        }
+    }
+
+    impl fmt::Display for Pin {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(
+                f,
+                "{}:{}@{},{}",
+                self.present_idx, self.poss_idx, self.x, self.y
+            )
+        }
+    }
+
+    impl fmt::Display for RegionSpec {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{}x{}:", self.width, self.height)?;
+            for count in &self.demand {
+                write!(f, " {count}")?;
+            }
+            if !self.blocked.is_empty() {
+                write!(f, " | blocked")?;
+                for (x, y) in &self.blocked {
+                    write!(f, " {x},{y}")?;
+                }
+            }
+            if !self.pinned.is_empty() {
+                write!(f, " | pinned")?;
+                for pin in &self.pinned {
+                    write!(f, " {pin}")?;
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_rectangle() {
+        let spec = RegionSpec::new("12x5: 1 0 1 0 2 2");
+
+        assert_eq!(spec.width, 12);
+        assert_eq!(spec.height, 5);
+        assert_eq!(spec.demand, vec![1, 0, 1, 0, 2, 2]);
+        assert!(spec.blocked.is_empty());
+        assert_eq!(spec.free_cells(), 60);
+    }
+
+    #[test]
+    fn parse_blocked_list() {
+        let spec = RegionSpec::new("12x5: 1 0 1 | blocked 11,4 0,0");
+
+        assert_eq!(spec.demand, vec![1, 0, 1]);
+        assert_eq!(spec.blocked, vec![(0, 0), (11, 4)]);
+        assert_eq!(spec.free_cells(), 58);
+    }
+
+    #[test]
+    fn parse_mask() {
+        let spec = RegionSpec::new("4x3: 0 0 1\n#...\n....\n..##");
+
+        assert_eq!(spec.width, 4);
+        assert_eq!(spec.height, 3);
+        assert_eq!(spec.blocked, vec![(0, 0), (2, 2), (3, 2)]);
+        assert_eq!(spec.free_cells(), 9);
+        assert_eq!(spec, RegionSpec::new("4x3: 0 0 1 | blocked 0,0 2,2 3,2"));
+    }
+
+    #[test]
+    fn parse_pinned() {
+        let spec = RegionSpec::new("12x5: 1 0 1 | pinned 2:3@4,1 0:0@0,0 | blocked 5,4");
+
+        assert_eq!(spec.blocked, vec![(5, 4)]);
+        assert_eq!(
+            spec.pinned,
+            vec![
+                Pin {
+                    present_idx: 2,
+                    poss_idx: 3,
+                    x: 4,
+                    y: 1
+                },
+                Pin {
+                    present_idx: 0,
+                    poss_idx: 0,
+                    x: 0,
+                    y: 0
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn display() {
+        for input in [
+            "12x5: 1 0 1 0 2 2",
+            "12x5: 1 0 1 | blocked 0,0 11,4",
+            "12x5: 1 0 1 | pinned 2:3@4,1 0:0@0,0",
+            "12x5: 1 0 1 | blocked 5,4 | pinned 2:3@4,1",
+            "12x5: 1 0 1 | pinned 2:3@-1,4 0:0@3,-1",
+        ] {
+            assert_eq!(RegionSpec::new(input).to_string(), input);
+        }
+        assert_eq!(
+            RegionSpec::new("4x2: 0 1\n#..#\n....").to_string(),
+            "4x2: 0 1 | blocked 0,0 3,0"
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        for input in [
+            "12by5: 1 0",
+            "12x5 1 0",
+            "12x5: 1 zero",
+            "12x5: 1 | blocked 12,0",
+            "12x5: 1 | blocked -1,0",
+            "12x5: 1 | pinned 0:0",
+            "12x5: 1 | frozen 0,0",
+            "2x1: 1\n#o",
+        ] {
+            assert!(RegionSpec::try_new(input).is_err(), "{input}");
+        }
+        assert_eq!(
+            RegionSpec::try_new("3x3: x").unwrap_err().to_string(),
+            "Parse error: Expected a number, found \"x\""
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn parse_mask_wrong_height() {
+        RegionSpec::new("4x3: 0 0 1\n#...\n....");
+    }
+}
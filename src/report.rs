@@ -0,0 +1,208 @@
+use std::fmt;
+
+use crate::{solver::Prunes, tree::RegionPlacement};
+
+// What a solve run found, one entry per region in input order
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Report {
+    pub regions: Vec<RegionReport>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Verdict {
+    Fits,
+    DoesNotFit,
+    // The solver gave up at its deadline without an answer
+    TimedOut,
+    // Every solver finished, but none with an answer it could stand by
+    Undecided,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RegionReport {
+    pub index: usize,
+    pub width: usize,
+    pub height: usize,
+    pub demand: Vec<usize>,
+    pub verdict: Verdict,
+    // Name of the check that settled the verdict, e.g. "area" or "backtrack"
+    pub decided_by: String,
+    // Whether the solver is sound for the verdict, rather than guessing as
+    // the area check does when it passes a region
+    pub proven: bool,
+    pub nodes: u64,
+    pub pruned: Prunes,
+    // Empty unless the solver that decided it builds a packing. Pins aren't
+    // repeated here.
+    pub placements: Vec<RegionPlacement>,
+    pub elapsed_micros: u64,
+}
+
+impl Report {
+    pub const CSV_HEADER: &str =
+        "index,width,height,demand,verdict,decided_by,proven,nodes,elapsed_micros";
+
+    pub fn passed(&self) -> usize {
+        self.regions.iter().filter(|region| region.fits()).count()
+    }
+
+    // Of those that pass, the ones only guessed to
+    pub fn unproven(&self) -> usize {
+        (self.regions.iter())
+            .filter(|region| region.fits() && !region.proven)
+            .count()
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = format!("{}\n", Self::CSV_HEADER);
+        for region in &self.regions {
+            csv.push_str(&region.to_csv_row());
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+impl Verdict {
+    // Fits or doesn't; a timeout or a guess leaves the question open
+    pub fn is_decided(&self) -> bool {
+        matches!(self, Self::Fits | Self::DoesNotFit)
+    }
+}
+
+impl RegionReport {
+    pub fn fits(&self) -> bool {
+        self.verdict == Verdict::Fits
+    }
+
+    pub fn nodes_per_second(&self) -> f64 {
+        if self.elapsed_micros == 0 {
+            return 0.0;
+        }
+        self.nodes as f64 * 1e6 / self.elapsed_micros as f64
+    }
+
+    // Demand is space separated so it stays one column
+    pub fn to_csv_row(&self) -> String {
+        let demand: Vec<String> = self.demand.iter().map(|count| count.to_string()).collect();
+        format!(
+            "{},{},{},{},{},{},{},{},{}",
+            self.index,
+            self.width,
+            self.height,
+            demand.join(" "),
+            self.verdict,
+            self.decided_by,
+            self.proven,
+            self.nodes,
+            self.elapsed_micros
+        )
+    }
+}
+
+mod formatting {
+    use super::*;
+
+    impl fmt::Display for Verdict {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Self::Fits => write!(f, "PASS"),
+                Self::DoesNotFit => write!(f, "FAIL"),
+                Self::TimedOut => write!(f, "TIMEOUT"),
+                Self::Undecided => write!(f, "UNDECIDED"),
+            }
+        }
+    }
+
+    impl fmt::Display for RegionReport {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "Region {} ({}x{}:", self.index, self.width, self.height)?;
+            for count in &self.demand {
+                write!(f, " {count}")?;
+            }
+            write!(f, ") {} by {}", self.verdict, self.decided_by)?;
+            if self.verdict.is_decided() && !self.proven {
+                write!(f, " (unproven)")?;
+            }
+            write!(f, ", {} nodes, {}us", self.nodes, self.elapsed_micros)
+        }
+    }
+
+    impl fmt::Display for Report {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            for region in &self.regions {
+                writeln!(f, "{region}")?;
+            }
+            write!(f, "{} of {} regions fit", self.passed(), self.regions.len())?;
+            match self.unproven() {
+                0 => writeln!(f),
+                unproven => writeln!(f, ", {unproven} of them unproven"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report() -> Report {
+        Report {
+            regions: vec![
+                RegionReport {
+                    index: 0,
+                    width: 4,
+                    height: 4,
+                    demand: vec![0, 2],
+                    verdict: Verdict::Fits,
+                    decided_by: "area".to_string(),
+                    proven: false,
+                    nodes: 0,
+                    pruned: Prunes::default(),
+                    placements: vec![],
+                    elapsed_micros: 5,
+                },
+                RegionReport {
+                    index: 1,
+                    width: 12,
+                    height: 5,
+                    demand: vec![1, 3],
+                    verdict: Verdict::TimedOut,
+                    decided_by: "backtrack".to_string(),
+                    proven: false,
+                    nodes: 1200,
+                    pruned: Prunes::default(),
+                    placements: vec![],
+                    elapsed_micros: 1000,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn text() {
+        assert_eq!(
+            report().to_string(),
+            "Region 0 (4x4: 0 2) PASS by area (unproven), 0 nodes, 5us\n\
+             Region 1 (12x5: 1 3) TIMEOUT by backtrack, 1200 nodes, 1000us\n\
+             1 of 2 regions fit, 1 of them unproven\n"
+        );
+    }
+
+    #[test]
+    fn csv() {
+        assert_eq!(
+            report().to_csv(),
+            "index,width,height,demand,verdict,decided_by,proven,nodes,elapsed_micros\n\
+             0,4,4,0 2,PASS,area,false,0,5\n\
+             1,12,5,1 3,TIMEOUT,backtrack,false,1200,1000\n"
+        );
+    }
+}
@@ -0,0 +1,67 @@
+// SplitMix64, so generated puzzles and randomised tests are reproducible from
+// a seed without pulling in a dependency
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in 0..bound
+    pub fn below(&mut self, bound: usize) -> usize {
+        assert!(bound > 0);
+        (self.next_u64() % bound as u64) as usize
+    }
+
+    // Uniform in the inclusive range
+    pub fn between(&mut self, low: usize, high: usize) -> usize {
+        assert!(low <= high);
+        low + self.below(high - low + 1)
+    }
+
+    pub fn chance(&mut self, numerator: usize, denominator: usize) -> bool {
+        self.below(denominator) < numerator
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deterministic() {
+        let mut rng = Rng::new(12);
+        let mut rng1 = Rng::new(12);
+        let mut rng2 = Rng::new(13);
+
+        let values: Vec<u64> = (0..5).map(|_| rng.next_u64()).collect();
+        let values1: Vec<u64> = (0..5).map(|_| rng1.next_u64()).collect();
+        let values2: Vec<u64> = (0..5).map(|_| rng2.next_u64()).collect();
+
+        assert_eq!(values, values1);
+        assert_ne!(values, values2);
+    }
+
+    #[test]
+    fn between() {
+        let mut rng = Rng::new(0);
+        let mut seen = [false; 4];
+        for _ in 0..200 {
+            let value = rng.between(3, 6);
+            assert!((3..=6).contains(&value));
+            seen[value - 3] = true;
+        }
+        assert_eq!(seen, [true; 4]);
+    }
+}
@@ -0,0 +1,611 @@
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    cache::VerdictCache,
+    presents::{PresentPossibilities, ShapeSet},
+    region::RegionSpec,
+    report::{RegionReport, Verdict},
+    tree::{Checkpoint, PinError, Placement, Search, Tree},
+};
+
+mod portfolio;
+mod progress;
+mod registry;
+pub use portfolio::{Mode, Portfolio};
+pub use progress::{Observer, PROGRESS_EVERY, Progress, Prunes};
+pub use registry::{Registry, UnknownSolver};
+
+// A way of deciding whether a region's demand fits. The tree already holds
+// the region, its pins and the shapes, and `cancel` says when to give up.
+pub trait Solver: Send + Sync {
+    fn name(&self) -> &str;
+    fn solve(&self, tree: &Tree, cancel: &Cancel) -> Outcome;
+
+    // Whether every verdict it reaches is right, rather than a guess such as
+    // the area check's
+    fn is_exact(&self) -> bool {
+        true
+    }
+
+    // Whether `verdict` from it can be taken as the answer. One that isn't
+    // exact can still rule a region out, as the area check does.
+    fn is_sound(&self, verdict: Verdict) -> bool {
+        verdict.is_decided() && (self.is_exact() || verdict == Verdict::DoesNotFit)
+    }
+
+    // `solve`, but carrying on from the checkpoint at `path` if there is one
+    // and writing a new one every so often and when cancelled. Solvers that
+    // can't stop and carry on just solve.
+    fn solve_resumable(
+        &self,
+        tree: &Tree,
+        cancel: &Cancel,
+        _path: &Path,
+        _every: Duration,
+    ) -> io::Result<Outcome> {
+        Ok(self.solve(tree, cancel))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Outcome {
+    pub verdict: Verdict,
+    pub decided_by: String,
+    pub nodes: u64,
+    pub pruned: Prunes,
+    pub placements: Vec<Placement>,
+}
+
+// When a search should stop: at a deadline, when another thread raises the
+// flag, or when whatever started it is cancelled in turn. Searches also
+// report their progress through it, to the nearest observer up the chain.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Cancel<'a> {
+    deadline: Option<Instant>,
+    flag: Option<&'a AtomicBool>,
+    observer: Option<(usize, &'a dyn Observer)>,
+    parent: Option<&'a Cancel<'a>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct SolveConfig {
+    pub solver: Arc<dyn Solver>,
+    // Regions solved at once
+    pub threads: usize,
+    // Per region
+    pub timeout: Option<Duration>,
+    // Shared by every region solved with this config
+    pub cache: Option<Arc<VerdictCache>>,
+    pub observer: Option<Arc<dyn Observer>>,
+    // Where searches keep a checkpoint per region, and how often they write it
+    pub checkpoint_dir: Option<PathBuf>,
+    pub checkpoint_every: Duration,
+}
+
+// Solving a region stops short when its pins don't fit, or when its
+// checkpoint can't be read or written
+#[derive(Debug)]
+pub enum SolveError {
+    Pin(PinError),
+    Checkpoint(io::Error),
+}
+
+// Passes a region when the demanded cells don't outnumber its free ones.
+// Only a region it fails is sure not to fit.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AreaBound;
+
+// Gives the placements too when they fit, so they can be checked
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NaiveBacktrack;
+
+// Also finds the placements, so the region can be drawn
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BranchAndBound;
+
+impl Outcome {
+    pub fn new(verdict: Verdict, decided_by: &str) -> Self {
+        Self {
+            verdict,
+            decided_by: decided_by.to_string(),
+            nodes: 0,
+            pruned: Prunes::default(),
+            placements: vec![],
+        }
+    }
+}
+
+impl<'a> Cancel<'a> {
+    pub fn never() -> Self {
+        Self::default()
+    }
+
+    pub fn at(deadline: Option<Instant>) -> Self {
+        Self {
+            deadline,
+            ..Self::default()
+        }
+    }
+
+    // Cancelled when `flag` is raised, or whenever `self` is
+    pub fn child(&'a self, flag: &'a AtomicBool) -> Self {
+        Self {
+            flag: Some(flag),
+            parent: Some(self),
+            ..Self::default()
+        }
+    }
+
+    // Cancelled whenever `self` is, with progress going to `observer` as
+    // that of `region`
+    pub fn observed(&'a self, region: usize, observer: &'a dyn Observer) -> Self {
+        Self {
+            observer: Some((region, observer)),
+            parent: Some(self),
+            ..Self::default()
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+            || self.flag.is_some_and(|flag| flag.load(Ordering::Relaxed))
+            || self.parent.is_some_and(Cancel::is_cancelled)
+    }
+
+    pub fn report(&self, progress: &Progress) {
+        match (self.observer, self.parent) {
+            (Some((region, observer)), _) => observer.progress(region, progress),
+            (None, Some(parent)) => parent.report(progress),
+            (None, None) => {}
+        }
+    }
+}
+
+impl Default for SolveConfig {
+    fn default() -> Self {
+        Self {
+            solver: Arc::new(AreaBound),
+            threads: 1,
+            timeout: None,
+            cache: None,
+            observer: None,
+            checkpoint_dir: None,
+            checkpoint_every: Duration::from_secs(60),
+        }
+    }
+}
+
+impl Solver for AreaBound {
+    fn name(&self) -> &str {
+        "area"
+    }
+
+    fn is_exact(&self) -> bool {
+        false
+    }
+
+    fn solve(&self, tree: &Tree, cancel: &Cancel) -> Outcome {
+        if tree.simple_check() {
+            Outcome::new(Verdict::Fits, self.name())
+        } else {
+            Outcome::new(Verdict::DoesNotFit, self.name())
+        }
+    }
+}
+
+impl Solver for NaiveBacktrack {
+    fn name(&self) -> &str {
+        "backtrack"
+    }
+
+    fn solve(&self, tree: &Tree, cancel: &Cancel) -> Outcome {
+        if let Some(outcome) = area_refutes(tree) {
+            return outcome;
+        }
+        let mut search = Search::new(tree);
+        let fits = search.run(cancel);
+        self.outcome(&search, fits)
+    }
+
+    fn solve_resumable(
+        &self,
+        tree: &Tree,
+        cancel: &Cancel,
+        path: &Path,
+        every: Duration,
+    ) -> io::Result<Outcome> {
+        if let Some(outcome) = area_refutes(tree) {
+            return Ok(outcome);
+        }
+        let mut search = match Checkpoint::load(path)? {
+            Some(checkpoint) => Search::resume(tree, &checkpoint)?,
+            None => Search::new(tree),
+        };
+        let fits = search.run_checkpointed(cancel, path, every)?;
+        Ok(self.outcome(&search, fits))
+    }
+}
+
+impl NaiveBacktrack {
+    // With the packing the search found, when it found one
+    fn outcome(&self, search: &Search, fits: Option<bool>) -> Outcome {
+        let verdict = match fits {
+            Some(true) => Verdict::Fits,
+            Some(false) => Verdict::DoesNotFit,
+            None => Verdict::TimedOut,
+        };
+        let progress = search.progress();
+        Outcome {
+            nodes: progress.nodes,
+            pruned: progress.pruned,
+            placements: match fits {
+                Some(true) => search.tree().placements(),
+                _ => vec![],
+            },
+            ..Outcome::new(verdict, self.name())
+        }
+    }
+}
+
+impl Solver for BranchAndBound {
+    fn name(&self) -> &str {
+        "pack"
+    }
+
+    fn solve(&self, tree: &Tree, cancel: &Cancel) -> Outcome {
+        if let Some(outcome) = area_refutes(tree) {
+            return outcome;
+        }
+        let (packing, progress) = tree.pack_cancellable(cancel);
+        let mut outcome = match packing {
+            Some(Some(packing)) => Outcome {
+                placements: packing.placements,
+                ..Outcome::new(Verdict::Fits, self.name())
+            },
+            Some(None) => Outcome::new(Verdict::DoesNotFit, self.name()),
+            None => Outcome::new(Verdict::TimedOut, self.name()),
+        };
+        outcome.nodes = progress.nodes;
+        outcome.pruned = progress.pruned;
+        outcome
+    }
+}
+
+// Nothing to search for when the presents can't even cover the area
+fn area_refutes(tree: &Tree) -> Option<Outcome> {
+    (tree.demanded_area() > tree.open_cell_count())
+        .then(|| Outcome::new(Verdict::DoesNotFit, AreaBound.name()))
+}
+
+pub fn solve_region(
+    index: usize,
+    spec: &RegionSpec,
+    shapes: &ShapeSet,
+    config: &SolveConfig,
+) -> Result<RegionReport, SolveError> {
+    let start = Instant::now();
+    let tree = Tree::try_from_spec(spec, shapes)?;
+
+    let solver = config.solver.name();
+    let cached = (config.cache.as_ref()).and_then(|cache| cache.lookup(shapes, solver, spec));
+    let outcome = match cached {
+        Some(verdict) => Outcome::new(verdict, "cache"),
+        None => {
+            let deadline = Cancel::at(config.timeout.map(|timeout| start + timeout));
+            let cancel = match &config.observer {
+                Some(observer) => deadline.observed(index, observer.as_ref()),
+                None => deadline,
+            };
+            let outcome = match &config.checkpoint_dir {
+                Some(dir) => config
+                    .solver
+                    .solve_resumable(
+                        &tree,
+                        &cancel,
+                        &dir.join(format!("region-{index}.checkpoint")),
+                        config.checkpoint_every,
+                    )
+                    .map_err(SolveError::Checkpoint)?,
+                None => config.solver.solve(&tree, &cancel),
+            };
+            if let Some(cache) = &config.cache {
+                cache.record(shapes, solver, spec, outcome.verdict);
+            }
+            outcome
+        }
+    };
+
+    Ok(RegionReport {
+        index,
+        width: spec.width,
+        height: spec.height,
+        demand: spec.demand.clone(),
+        verdict: outcome.verdict,
+        decided_by: outcome.decided_by,
+        // A cached verdict is only as good as the solver that reached it
+        proven: config.solver.is_sound(outcome.verdict),
+        nodes: outcome.nodes,
+        pruned: outcome.pruned,
+        placements: (outcome.placements.iter())
+            .map(|placement| tree.region_placement(placement))
+            .collect(),
+        elapsed_micros: start.elapsed().as_micros() as u64,
+    })
+}
+
+// One thread per region, with the reports back in the order of `batch`
+pub fn solve_batch(
+    batch: &[RegionSpec],
+    shapes: &ShapeSet,
+    config: &SolveConfig,
+    first_index: usize,
+) -> Vec<Result<RegionReport, SolveError>> {
+    if batch.len() == 1 {
+        return vec![solve_region(first_index, &batch[0], shapes, config)];
+    }
+    thread::scope(|scope| {
+        let handles: Vec<_> = batch
+            .iter()
+            .enumerate()
+            .map(|(offset, spec)| {
+                scope.spawn(move || solve_region(first_index + offset, spec, shapes, config))
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect()
+    })
+}
+
+impl From<PinError> for SolveError {
+    fn from(err: PinError) -> Self {
+        Self::Pin(err)
+    }
+}
+
+impl std::error::Error for SolveError {}
+
+mod formatting {
+    use super::*;
+
+    impl fmt::Display for SolveError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Self::Pin(err) => write!(f, "{err}"),
+                Self::Checkpoint(err) => write!(f, "Checkpoint: {err}"),
+            }
+        }
+    }
+
+    impl fmt::Debug for dyn Solver {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{}", self.name())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::presents::OrientationPolicy;
+
+    fn shapes() -> ShapeSet {
+        ShapeSet::new(vec![PresentPossibilities::new("###\n#..\n###")])
+    }
+
+    fn config<S: Solver + 'static>(solver: S) -> SolveConfig {
+        SolveConfig {
+            solver: Arc::new(solver),
+            ..SolveConfig::default()
+        }
+    }
+
+    #[test]
+    fn verdicts() {
+        let shapes = shapes();
+        let spec = RegionSpec::new("4x4: 2");
+
+        let area = solve_region(0, &spec, &shapes, &config(AreaBound)).unwrap();
+        let backtrack = solve_region(0, &spec, &shapes, &config(NaiveBacktrack)).unwrap();
+        let pack = solve_region(0, &spec, &shapes, &config(BranchAndBound)).unwrap();
+
+        assert_eq!(area.verdict, Verdict::Fits);
+        assert_eq!(area.decided_by, "area");
+        assert_eq!(area.nodes, 0);
+        assert_eq!(backtrack.verdict, Verdict::Fits);
+        assert_eq!(backtrack.decided_by, "backtrack");
+        assert!(backtrack.nodes > 0);
+        assert_eq!(pack.placements.len(), 2);
+        assert_eq!((pack.width, pack.height, pack.demand), (4, 4, vec![2]));
+    }
+
+    // Whatever a report says was placed can be written back as pins, even
+    // where the box hangs over the edge
+    #[test]
+    fn placements_pin_back() {
+        let overhang = PresentPossibilities::with_policy(".##\n.##\n...", OrientationPolicy::Fixed);
+        let mut overhung = false;
+        for (shapes, input) in [
+            (ShapeSet::new(vec![overhang]), "2x2: 1"),
+            (shapes(), "4x4: 2"),
+            (shapes(), "3x7: 2"),
+        ] {
+            let spec = RegionSpec::new(input);
+            let report = solve_region(0, &spec, &shapes, &config(BranchAndBound)).unwrap();
+            assert_eq!(report.verdict, Verdict::Fits, "{input}");
+
+            let mut pinned = spec.clone();
+            pinned.pinned = report
+                .placements
+                .iter()
+                .map(|&placement| placement.into())
+                .collect();
+            let replayed = RegionSpec::new(&pinned.to_string());
+            let tree = Tree::from_spec(&replayed, &shapes);
+
+            assert_eq!(replayed, pinned);
+            assert_eq!(tree.check_certificate(&replayed, &[]), Ok(()), "{input}");
+            overhung |= replayed.pinned.iter().any(|pin| pin.x < 0 || pin.y < 0);
+        }
+        assert!(overhung);
+    }
+
+    #[test]
+    fn area_decides_first() {
+        let shapes = shapes();
+        let spec = RegionSpec::new("4x4: 3");
+
+        let report = solve_region(3, &spec, &shapes, &config(NaiveBacktrack)).unwrap();
+
+        assert_eq!(report.index, 3);
+        assert_eq!(report.verdict, Verdict::DoesNotFit);
+        assert_eq!(report.decided_by, "area");
+        assert_eq!(report.nodes, 0);
+    }
+
+    #[test]
+    fn timeout() {
+        let shapes = ShapeSet::new(vec![PresentPossibilities::new("###\n#..\n#..")]);
+        let spec = RegionSpec::new("9x9: 6");
+
+        for solver in [
+            Arc::new(NaiveBacktrack) as Arc<dyn Solver>,
+            Arc::new(BranchAndBound),
+        ] {
+            let config = SolveConfig {
+                solver,
+                timeout: Some(Duration::ZERO),
+                ..SolveConfig::default()
+            };
+            let report = solve_region(0, &spec, &shapes, &config).unwrap();
+            assert_eq!(report.verdict, Verdict::TimedOut);
+        }
+    }
+
+    #[test]
+    fn cached() {
+        let shapes = shapes();
+        let config = SolveConfig {
+            cache: Some(Arc::new(VerdictCache::new())),
+            ..config(NaiveBacktrack)
+        };
+
+        let first = solve_region(0, &RegionSpec::new("4x4: 2"), &shapes, &config).unwrap();
+        let second = solve_region(1, &RegionSpec::new("5x4: 1"), &shapes, &config).unwrap();
+        let blocked = RegionSpec::new("5x4: 1 | blocked 0,0");
+        let third = solve_region(2, &blocked, &shapes, &config).unwrap();
+
+        assert_eq!(first.decided_by, "backtrack");
+        assert_eq!(second.verdict, Verdict::Fits);
+        assert_eq!(second.decided_by, "cache");
+        assert_eq!(second.nodes, 0);
+        assert_eq!(third.decided_by, "backtrack");
+    }
+
+    #[test]
+    fn checkpointed() {
+        let shapes = ShapeSet::new(vec![PresentPossibilities::new("###\n#..\n#..")]);
+        let spec = RegionSpec::new("6x6: 6");
+        let dir = std::env::temp_dir().join(format!("checkpoints-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("region-4.checkpoint");
+        let stopped = SolveConfig {
+            timeout: Some(Duration::ZERO),
+            checkpoint_dir: Some(dir.clone()),
+            ..config(NaiveBacktrack)
+        };
+
+        let first = solve_region(4, &spec, &shapes, &stopped).unwrap();
+        assert_eq!(first.verdict, Verdict::TimedOut);
+        assert!(path.exists());
+
+        let resumed = SolveConfig {
+            timeout: None,
+            ..stopped
+        };
+        let second = solve_region(4, &spec, &shapes, &resumed).unwrap();
+        let whole = solve_region(4, &spec, &shapes, &config(NaiveBacktrack)).unwrap();
+        assert_eq!(second.verdict, Verdict::Fits);
+        assert_eq!(second.nodes, whole.nodes);
+        assert!(!path.exists());
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        seen: std::sync::Mutex<Vec<(usize, Progress)>>,
+    }
+
+    impl Observer for Recorder {
+        fn progress(&self, region: usize, progress: &Progress) {
+            self.seen.lock().unwrap().push((region, *progress));
+        }
+    }
+
+    #[test]
+    fn observed() {
+        let shapes = ShapeSet::new(vec![PresentPossibilities::new("###\n#..\n#..")]);
+        let recorder = Arc::new(Recorder::default());
+        let config = SolveConfig {
+            observer: Some(recorder.clone()),
+            ..config(NaiveBacktrack)
+        };
+
+        let report = solve_region(2, &RegionSpec::new("6x6: 7"), &shapes, &config).unwrap();
+
+        let seen = recorder.seen.lock().unwrap();
+        assert_eq!(report.verdict, Verdict::DoesNotFit);
+        assert_eq!(seen.len() as u64, report.nodes / PROGRESS_EVERY);
+        assert!(seen.iter().all(|(region, progress)| {
+            *region == 2 && progress.nodes <= report.nodes && progress.depth <= 7
+        }));
+        assert!(report.pruned.collision > 0);
+        assert!(report.pruned.pocket > 0);
+        assert_eq!(report.pruned.bound, 0);
+    }
+
+    #[test]
+    fn cancel() {
+        let outer = AtomicBool::new(false);
+        let inner = AtomicBool::new(false);
+        let root = Cancel::never();
+        let parent = root.child(&outer);
+        let child = parent.child(&inner);
+
+        assert!(!child.is_cancelled());
+        outer.store(true, Ordering::Relaxed);
+        assert!(child.is_cancelled());
+        assert!(Cancel::at(Some(Instant::now())).is_cancelled());
+        assert!(!Cancel::never().is_cancelled());
+    }
+
+    #[test]
+    fn batch_order() {
+        let shapes = shapes();
+        let batch = vec![
+            RegionSpec::new("4x4: 3"),
+            RegionSpec::new("4x4: 2"),
+            RegionSpec::new("8x4: 1"),
+        ];
+
+        let reports: Vec<RegionReport> = solve_batch(&batch, &shapes, &config(NaiveBacktrack), 10)
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+
+        let indices: Vec<usize> = reports.iter().map(|report| report.index).collect();
+        assert_eq!(indices, vec![10, 11, 12]);
+        assert_eq!(reports[0].verdict, Verdict::DoesNotFit);
+        assert_eq!(reports[2].width, 8);
+    }
+}
@@ -0,0 +1,251 @@
+use std::sync::mpsc;
+
+use super::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    // One after another until one decides in a way it is sound for
+    Sequence,
+    // All at once, each on its own thread; the first to decide in a way it
+    // is sound for stops the rest
+    Race,
+}
+
+// Several solvers behind one name, such as "area,backtrack" or "race:backtrack,pack"
+#[derive(Clone)]
+pub struct Portfolio {
+    mode: Mode,
+    solvers: Vec<Arc<dyn Solver>>,
+    name: String,
+}
+
+impl Portfolio {
+    pub fn new(mode: Mode, solvers: Vec<Arc<dyn Solver>>) -> Self {
+        let names: Vec<&str> = solvers.iter().map(|solver| solver.name()).collect();
+        let name = match mode {
+            Mode::Sequence => names.join(","),
+            Mode::Race => format!("race:{}", names.join(",")),
+        };
+        Self {
+            mode,
+            solvers,
+            name,
+        }
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    fn sequence(&self, tree: &Tree, cancel: &Cancel) -> Outcome {
+        let mut nodes = 0;
+        let mut pruned = Prunes::default();
+        for solver in &self.solvers {
+            let outcome = solver.solve(tree, cancel);
+            nodes += outcome.nodes;
+            pruned += outcome.pruned;
+            if solver.is_sound(outcome.verdict) {
+                return Outcome {
+                    nodes,
+                    pruned,
+                    ..outcome
+                };
+            }
+            if cancel.is_cancelled() {
+                break;
+            }
+        }
+        Outcome {
+            nodes,
+            pruned,
+            ..self.undecided(cancel)
+        }
+    }
+
+    // Out of time only when something stopped it; otherwise every solver
+    // had its say and none could stand by it
+    fn undecided(&self, cancel: &Cancel) -> Outcome {
+        let verdict = if cancel.is_cancelled() {
+            Verdict::TimedOut
+        } else {
+            Verdict::Undecided
+        };
+        Outcome::new(verdict, &self.name)
+    }
+
+    // Outcomes arrive over the channel in the order they finish, so the
+    // first one its solver is sound for is the winner. Nodes and prunes
+    // count everyone's work.
+    fn race(&self, tree: &Tree, cancel: &Cancel) -> Outcome {
+        let stop = AtomicBool::new(false);
+        let child = cancel.child(&stop);
+        let (sender, receiver) = mpsc::channel();
+
+        thread::scope(|scope| {
+            for solver in &self.solvers {
+                let sender = sender.clone();
+                let (child, stop) = (&child, &stop);
+                scope.spawn(move || {
+                    let outcome = solver.solve(tree, child);
+                    let wins = solver.is_sound(outcome.verdict);
+                    if wins {
+                        stop.store(true, Ordering::Relaxed);
+                    }
+                    sender.send((wins, outcome)).unwrap();
+                });
+            }
+            drop(sender);
+
+            let mut nodes = 0;
+            let mut pruned = Prunes::default();
+            let mut winner = None;
+            for (wins, outcome) in receiver {
+                nodes += outcome.nodes;
+                pruned += outcome.pruned;
+                if winner.is_none() && wins {
+                    winner = Some(outcome);
+                }
+            }
+            let winner = winner.unwrap_or_else(|| self.undecided(cancel));
+            Outcome {
+                nodes,
+                pruned,
+                ..winner
+            }
+        })
+    }
+}
+
+// Exact whatever it's made of, since it only passes on what its solvers
+// are sound for
+impl Solver for Portfolio {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn solve(&self, tree: &Tree, cancel: &Cancel) -> Outcome {
+        match self.mode {
+            Mode::Sequence => self.sequence(tree, cancel),
+            Mode::Race => self.race(tree, cancel),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Never answers, and spins until told to stop
+    struct Stubborn;
+
+    impl Solver for Stubborn {
+        fn name(&self) -> &str {
+            "stubborn"
+        }
+
+        fn solve(&self, tree: &Tree, cancel: &Cancel) -> Outcome {
+            while !cancel.is_cancelled() {
+                thread::yield_now();
+            }
+            Outcome::new(Verdict::TimedOut, self.name())
+        }
+    }
+
+    fn shapes() -> Vec<PresentPossibilities> {
+        vec![PresentPossibilities::new("###\n#..\n###")]
+    }
+
+    #[test]
+    fn sequence() {
+        let shapes = shapes();
+        let tree = Tree::new("4x4: 2", &shapes);
+        let portfolio = Portfolio::new(
+            Mode::Sequence,
+            vec![Arc::new(NaiveBacktrack), Arc::new(BranchAndBound)],
+        );
+
+        let outcome = portfolio.solve(&tree, &Cancel::never());
+
+        assert_eq!(portfolio.name(), "backtrack,pack");
+        assert_eq!(outcome.verdict, Verdict::Fits);
+        assert_eq!(outcome.decided_by, "backtrack");
+        assert_eq!(outcome.placements.len(), 2);
+    }
+
+    #[test]
+    fn sequence_gives_up() {
+        let shapes = shapes();
+        let tree = Tree::new("4x4: 2", &shapes);
+        let portfolio = Portfolio::new(Mode::Sequence, vec![Arc::new(Stubborn)]);
+
+        let outcome = portfolio.solve(&tree, &Cancel::at(Some(Instant::now())));
+
+        assert_eq!(outcome.verdict, Verdict::TimedOut);
+        assert_eq!(outcome.decided_by, "stubborn");
+    }
+
+    // The area check passes "3x5: 2", but the two don't fit side by side
+    #[test]
+    fn guesses_dont_decide() {
+        let shapes = shapes();
+        let tree = Tree::new("3x5: 2", &shapes);
+        assert_eq!(
+            AreaBound.solve(&tree, &Cancel::never()).verdict,
+            Verdict::Fits
+        );
+
+        for mode in [Mode::Sequence, Mode::Race] {
+            let portfolio =
+                Portfolio::new(mode, vec![Arc::new(AreaBound), Arc::new(NaiveBacktrack)]);
+
+            let outcome = portfolio.solve(&tree, &Cancel::never());
+
+            assert_eq!(outcome.verdict, Verdict::DoesNotFit, "{mode:?}");
+            assert_eq!(outcome.decided_by, "backtrack", "{mode:?}");
+        }
+
+        // It is right to rule "3x3: 2" out, and nothing else need run
+        let tree = Tree::new("3x3: 2", &shapes);
+        for mode in [Mode::Sequence, Mode::Race] {
+            let portfolio = Portfolio::new(mode, vec![Arc::new(AreaBound), Arc::new(Stubborn)]);
+
+            let outcome = portfolio.solve(&tree, &Cancel::never());
+
+            assert_eq!(outcome.verdict, Verdict::DoesNotFit, "{mode:?}");
+            assert_eq!(outcome.decided_by, "area", "{mode:?}");
+        }
+    }
+
+    // Nothing ran out of time, so saying it did would be wrong
+    #[test]
+    fn undecided() {
+        let shapes = shapes();
+        let tree = Tree::new("3x5: 2", &shapes);
+
+        for mode in [Mode::Sequence, Mode::Race] {
+            let portfolio = Portfolio::new(mode, vec![Arc::new(AreaBound)]);
+
+            let outcome = portfolio.solve(&tree, &Cancel::never());
+
+            assert_eq!(outcome.verdict, Verdict::Undecided, "{mode:?}");
+            assert!(!outcome.verdict.is_decided());
+        }
+    }
+
+    #[test]
+    fn race() {
+        let shapes = shapes();
+        let tree = Tree::new("4x4: 2", &shapes);
+        let portfolio = Portfolio::new(
+            Mode::Race,
+            vec![Arc::new(Stubborn), Arc::new(BranchAndBound)],
+        );
+
+        let outcome = portfolio.solve(&tree, &Cancel::never());
+
+        assert_eq!(portfolio.name(), "race:stubborn,pack");
+        assert_eq!(outcome.verdict, Verdict::Fits);
+        assert_eq!(outcome.decided_by, "pack");
+        assert_eq!(outcome.placements.len(), 2);
+    }
+}
@@ -0,0 +1,94 @@
+use std::ops::AddAssign;
+
+use super::*;
+
+// Searches tell the observer how they're getting on every this many nodes
+pub const PROGRESS_EVERY: u64 = 1 << 12;
+
+// Hears how the searches of a solve are getting on, from whichever threads
+// they run on
+pub trait Observer: Send + Sync {
+    fn progress(&self, region: usize, progress: &Progress);
+}
+
+// Branches a search cut off, by why
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Prunes {
+    // A placement ran into something already there
+    pub collision: u64,
+    // The region had less room than the demand to begin with
+    pub slack: u64,
+    // Pockets left too little room for the rest of the demand
+    pub pocket: u64,
+    // Nothing below could beat the best packing found
+    pub bound: u64,
+}
+
+// A search as it goes, or as it ended up
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Progress {
+    pub nodes: u64,
+    // Placements, and cells left empty, on the way to the current node
+    pub depth: usize,
+    pub pruned: Prunes,
+    // Since the search started in this process
+    pub nodes_per_second: f64,
+}
+
+impl Progress {
+    pub fn new(nodes: u64, depth: usize, pruned: Prunes, since: (Instant, u64)) -> Self {
+        let (start, start_nodes) = since;
+        let seconds = start.elapsed().as_secs_f64();
+        let nodes_per_second = if seconds > 0.0 {
+            (nodes - start_nodes) as f64 / seconds
+        } else {
+            0.0
+        };
+        Self {
+            nodes,
+            depth,
+            pruned,
+            nodes_per_second,
+        }
+    }
+}
+
+impl AddAssign for Prunes {
+    fn add_assign(&mut self, other: Self) {
+        self.collision += other.collision;
+        self.slack += other.slack;
+        self.pocket += other.pocket;
+        self.bound += other.bound;
+    }
+}
+
+mod formatting {
+    use super::*;
+
+    impl fmt::Display for Progress {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(
+                f,
+                "{} nodes at depth {}, {:.0} nodes/s, pruned {}",
+                self.nodes, self.depth, self.nodes_per_second, self.pruned
+            )
+        }
+    }
+
+    impl fmt::Display for Prunes {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(
+                f,
+                "{} collision, {} slack, {} pocket, {} bound",
+                self.collision, self.slack, self.pocket, self.bound
+            )
+        }
+    }
+
+    impl fmt::Debug for dyn Observer + '_ {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "Observer")
+        }
+    }
+}
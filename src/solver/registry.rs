@@ -0,0 +1,118 @@
+use super::*;
+
+// Solvers by name, for picking one from the command line
+#[derive(Clone)]
+pub struct Registry {
+    solvers: Vec<Arc<dyn Solver>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnknownSolver {
+    pub name: String,
+    pub available: Vec<String>,
+}
+
+impl Registry {
+    pub fn empty() -> Self {
+        Self { solvers: vec![] }
+    }
+
+    // A solver under a name that's already taken replaces the old one
+    pub fn register(&mut self, solver: Arc<dyn Solver>) {
+        match self.position(solver.name()) {
+            Some(idx) => self.solvers[idx] = solver,
+            None => self.solvers.push(solver),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Solver>> {
+        self.position(name).map(|idx| self.solvers[idx].clone())
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.solvers.iter().map(|solver| solver.name()).collect()
+    }
+
+    // "name" for one solver, "a,b" to try them in turn, "race:a,b" to run
+    // them side by side
+    pub fn build(&self, spec: &str) -> Result<Arc<dyn Solver>, UnknownSolver> {
+        let (mode, names) = match spec.strip_prefix("race:") {
+            Some(names) => (Mode::Race, names),
+            None => (Mode::Sequence, spec),
+        };
+        let solvers = names
+            .split(',')
+            .map(|name| {
+                self.get(name.trim()).ok_or_else(|| UnknownSolver {
+                    name: name.trim().to_string(),
+                    available: self.names().into_iter().map(str::to_string).collect(),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if mode == Mode::Sequence && solvers.len() == 1 {
+            Ok(solvers.into_iter().next().unwrap())
+        } else {
+            Ok(Arc::new(Portfolio::new(mode, solvers)))
+        }
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.solvers.iter().position(|solver| solver.name() == name)
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register(Arc::new(AreaBound));
+        registry.register(Arc::new(NaiveBacktrack));
+        registry.register(Arc::new(BranchAndBound));
+        registry
+    }
+}
+
+impl std::error::Error for UnknownSolver {}
+
+mod formatting {
+    use super::*;
+
+    impl fmt::Display for UnknownSolver {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(
+                f,
+                "Unknown solver {:?}, expected one of {}",
+                self.name,
+                self.available.join(", ")
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build() {
+        let registry = Registry::default();
+
+        assert_eq!(registry.names(), vec!["area", "backtrack", "pack"]);
+        assert_eq!(registry.build("pack").unwrap().name(), "pack");
+        assert_eq!(
+            registry.build("area,backtrack").unwrap().name(),
+            "area,backtrack"
+        );
+        assert_eq!(
+            registry.build("race:backtrack,pack").unwrap().name(),
+            "race:backtrack,pack"
+        );
+
+        let err = registry.build("area,magic").unwrap_err();
+        assert_eq!(err.name, "magic");
+        assert_eq!(
+            err.to_string(),
+            "Unknown solver \"magic\", expected one of area, backtrack, pack"
+        );
+    }
+}
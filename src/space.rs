@@ -1,9 +1,14 @@
 use std::fmt::{Debug, Display, Formatter, Result};
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Space {
+    #[cfg_attr(feature = "serde", serde(rename = "#"))]
     Occupied,
+    // Free, but walled in with too few cells for any present still demanded
+    #[cfg_attr(feature = "serde", serde(rename = "o"))]
     Pocket,
+    #[cfg_attr(feature = "serde", serde(rename = "."))]
     Free,
 }
 
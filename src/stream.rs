@@ -0,0 +1,255 @@
+use std::io::{self, BufRead, Lines};
+
+use crate::{
+    parse_error::ParseError,
+    presents::{OrientationPolicy, PresentPossibilities, ShapeSet},
+    puzzle::check_demand,
+    region::RegionSpec,
+    report::RegionReport,
+    solver::{SolveConfig, SolveError, solve_batch},
+};
+
+// Reads the shapes up front, then hands out one region at a time as its lines
+// come in, so only the current region is ever held in memory. Mask rows are
+// attached to the header above them, skipping blank lines between, so the
+// next header is only known once it is read: one line of lookahead.
+pub struct RegionStream<R> {
+    lines: Lines<R>,
+    pub shapes: Vec<PresentPossibilities>,
+    pending: Option<String>,
+}
+
+impl<R: BufRead> RegionStream<R> {
+    pub fn new(reader: R) -> io::Result<Self> {
+        let mut lines = reader.lines();
+        let mut shapes: Vec<PresentPossibilities> = Vec::new();
+        let mut default_policy = OrientationPolicy::default();
+        let mut pending: Option<String> = None;
+
+        while let Some(line) = lines.next() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if is_region_header(&line) {
+                pending = Some(line);
+                break;
+            }
+            if let Some(policy) = line.strip_prefix("orientations:") {
+                default_policy = OrientationPolicy::try_new(policy).map_err(invalid_data)?;
+                continue;
+            }
+
+            let mut section = line;
+            for line in lines.by_ref() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    break;
+                }
+                section.push('\n');
+                section.push_str(&line);
+            }
+            shapes.push(
+                PresentPossibilities::try_from_section(&section, default_policy)
+                    .map_err(invalid_data)?,
+            );
+        }
+
+        Ok(Self {
+            lines,
+            shapes,
+            pending,
+        })
+    }
+}
+
+impl<R: BufRead> Iterator for RegionStream<R> {
+    type Item = io::Result<RegionSpec>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut description = match self.pending.take() {
+            Some(header) => header,
+            None => loop {
+                match self.lines.next()? {
+                    Ok(line) if line.trim().is_empty() => continue,
+                    Ok(line) => break line,
+                    Err(err) => return Some(Err(err)),
+                }
+            },
+        };
+
+        for line in self.lines.by_ref() {
+            let line = match line {
+                Ok(line) => line,
+                Err(err) => return Some(Err(err)),
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            if line.contains(':') {
+                self.pending = Some(line);
+                break;
+            }
+            description.push('\n');
+            description.push_str(&line);
+        }
+
+        let spec = RegionSpec::try_new(&description)
+            .and_then(|spec| check_demand(&spec, self.shapes.len()).map(|_| spec))
+            .map_err(invalid_data);
+        Some(spec)
+    }
+}
+
+// Solves regions as they are read and hands each report to `on_region`
+// straight away, `config.threads` regions at a time
+pub fn solve_stream<R: BufRead, F: FnMut(RegionReport) -> io::Result<()>>(
+    reader: R,
+    config: &SolveConfig,
+    on_region: F,
+) -> io::Result<()> {
+    let mut regions = RegionStream::new(reader)?;
+    let shapes = ShapeSet::from(&regions.shapes);
+    solve_regions(&shapes, &mut regions, config, on_region)
+}
+
+// Same as `solve_stream` for regions that come from elsewhere. A pin that
+// doesn't fit its region is an `InvalidData` error, like a parse error, and
+// checkpoint errors keep their kind.
+pub fn solve_regions<I, F>(
+    shapes: &ShapeSet,
+    regions: I,
+    config: &SolveConfig,
+    mut on_region: F,
+) -> io::Result<()>
+where
+    I: IntoIterator<Item = io::Result<RegionSpec>>,
+    F: FnMut(RegionReport) -> io::Result<()>,
+{
+    let mut regions = regions.into_iter();
+    let mut first_index = 0;
+    loop {
+        let batch: Vec<RegionSpec> = regions
+            .by_ref()
+            .take(config.threads.max(1))
+            .collect::<io::Result<Vec<RegionSpec>>>()?;
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        for (offset, report) in solve_batch(&batch, shapes, config, first_index)
+            .into_iter()
+            .enumerate()
+        {
+            let report = report.map_err(|err| {
+                let kind = match &err {
+                    SolveError::Pin(_) => io::ErrorKind::InvalidData,
+                    SolveError::Checkpoint(err) => err.kind(),
+                };
+                io::Error::new(kind, format!("Region {}: {err}", first_index + offset))
+            })?;
+            on_region(report)?;
+        }
+        first_index += batch.len();
+    }
+}
+
+// Parse errors come out as `InvalidData`, alongside any read errors
+fn invalid_data(err: ParseError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+fn is_region_header(line: &str) -> bool {
+    line.split_once(':')
+        .is_some_and(|(size, _)| size.trim().contains('x'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{file_parser::FileParser, puzzle::Puzzle};
+
+    #[test]
+    fn matches_puzzle() {
+        let my_file = FileParser::new("data/example.txt");
+        let puzzle = Puzzle::new(my_file.get_str());
+
+        let stream = RegionStream::new(my_file.get_str().as_bytes()).unwrap();
+
+        assert_eq!(stream.shapes, puzzle.shapes);
+        let regions: Vec<RegionSpec> = stream.map(Result::unwrap).collect();
+        assert_eq!(regions, puzzle.regions);
+    }
+
+    #[test]
+    fn extended_format() {
+        let input = "orientations: rotations\n\n\
+                     0:\n###\n#..\n###\n\n\
+                     1: dihedral\n##.\n...\n...\n\n\
+                     4x3: 1 0\n#...\n....\n...#\n\
+                     5x3: 1 1 | pinned 0:1@0,0\n\
+                     4x4: 0 2\n";
+        let puzzle = Puzzle::new(input);
+
+        let stream = RegionStream::new(input.as_bytes()).unwrap();
+
+        assert_eq!(stream.shapes, puzzle.shapes);
+        let regions: Vec<RegionSpec> = stream.map(Result::unwrap).collect();
+        assert_eq!(regions, puzzle.regions);
+        assert_eq!(regions[0].blocked, vec![(0, 0), (3, 2)]);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let input = "0:\n###\n#..\n###\n\n4x4: 1\n4x4: 1 1\n4x4: 2";
+
+        let results: Vec<io::Result<RegionSpec>> =
+            RegionStream::new(input.as_bytes()).unwrap().collect();
+
+        assert_eq!(results.len(), 3);
+        assert!(results[0].is_ok());
+        assert_eq!(
+            results[1].as_ref().unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+        assert!(results[2].is_ok());
+        assert!(RegionStream::new("0: upside\n###\n\n1x1: 0".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn solve() {
+        let my_file = FileParser::new("data/example.txt");
+        let mut reports: Vec<RegionReport> = Vec::new();
+
+        solve_stream(
+            my_file.get_str().as_bytes(),
+            &SolveConfig::default(),
+            |report| {
+                reports.push(report);
+                Ok(())
+            },
+        )
+        .unwrap();
+
+        let indices: Vec<usize> = reports.iter().map(|report| report.index).collect();
+        assert_eq!(indices, vec![0, 1, 2]);
+        assert!(reports.iter().all(|report| report.fits() && !report.proven));
+        assert_eq!(reports[2].demand, vec![1, 0, 1, 0, 3, 2]);
+    }
+
+    #[test]
+    fn solve_pin_error() {
+        let input = "0:\n###\n#..\n###\n\n4x4: 1\n4x4: 1 | pinned 0:0@2,2\n";
+        let mut seen = 0;
+
+        let err = solve_stream(input.as_bytes(), &SolveConfig::default(), |_| {
+            seen += 1;
+            Ok(())
+        })
+        .unwrap_err();
+
+        assert_eq!(seen, 1);
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().starts_with("Region 1: Cannot pin"));
+    }
+}
@@ -1,31 +1,96 @@
-use crate::{presents::PresentPossibilities, space::Space};
+use crate::{
+    presents::{OrientationPolicy, Present, PresentPossibilities, ShapeSet},
+    region::RegionSpec,
+    solver::{Cancel, Progress},
+    space::Space,
+};
 
 use std::{
     collections::{HashSet, VecDeque},
     hash::Hash,
+    sync::Arc,
+    time::Instant,
 };
 
-// Present_idx, Possibility_idx
-type PossibleMove = (usize, usize);
+mod certificate;
+mod optimise;
+mod pins;
+mod placement_table;
+mod pockets;
+mod render;
+mod search;
+mod symmetry;
+mod trail;
+pub use certificate::CertificateError;
+pub use optimise::Packing;
+pub use pins::PinError;
+pub use placement_table::PlacementTable;
+pub use search::{Checkpoint, Search};
+
+// Arguments to `place_present`, kept so a search can report what it placed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Placement {
+    pub present_idx: usize,
+    pub poss_idx: usize,
+    pub pos_x: usize,
+    pub pos_y: usize,
+}
+
+// A `Placement` as the input would put it, for reports: the orientation is
+// numbered as for a pin, and x, y is the region cell under the top left of
+// the 3x3 box. That is -1 when the box hangs over the left or top edge,
+// which it can when the orientation's first column or row is empty.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RegionPlacement {
+    pub present_idx: usize,
+    pub poss_idx: usize,
+    pub x: isize,
+    pub y: isize,
+}
 
-#[derive(Debug)]
-pub struct Tree<'a> {
+#[derive(Clone, Debug)]
+pub struct Tree {
     grid: Vec<Vec<Space>>,
-    present_types: &'a Vec<PresentPossibilities>,
+    present_types: ShapeSet,
     demand: Vec<usize>,
     state: Vec<usize>,
     space_slack: isize,
+    transposed: bool,
+    pinned: Vec<Placement>,
+    // Cells as they were before each change, with one step per placement
+    // pointing into it, so placements can be taken back in order
+    trail: Vec<(usize, usize, Space)>,
+    steps: Vec<trail::Step>,
+    undone: Vec<Placement>,
 }
 
-impl<'a> Tree<'a> {
-    pub fn new(input: &str, present_types: &'a Vec<PresentPossibilities>) -> Self {
-        let (size_string, demand_string) = input.split_once(": ").unwrap();
+impl Tree {
+    pub fn new(input: &str, present_types: impl Into<ShapeSet>) -> Self {
+        Self::from_spec(&RegionSpec::new(input), present_types)
+    }
+
+    pub fn with_size(
+        width: usize,
+        height: usize,
+        demand: Vec<usize>,
+        present_types: impl Into<ShapeSet>,
+    ) -> Self {
+        Self::from_spec(&RegionSpec::rectangle(width, height, demand), present_types)
+    }
+
+    pub fn from_spec(spec: &RegionSpec, present_types: impl Into<ShapeSet>) -> Self {
+        Self::try_from_spec(spec, present_types).unwrap_or_else(|err| panic!("{err}"))
+    }
 
-        // Create the space vector so it is wider than it is tall
-        let (height, width) = size_string.split_once('x').unwrap();
-        let mut height: usize = height.parse::<usize>().unwrap() + 2;
-        let mut width: usize = width.parse::<usize>().unwrap() + 2;
-        if height > width {
+    fn unpinned(spec: &RegionSpec, present_types: ShapeSet) -> Self {
+        // Create the space vector so it is wider than it is tall, unless some
+        // present can't be mirrored to match
+        let mut height = spec.height + 2;
+        let mut width = spec.width + 2;
+        let transposed = height > width && present_types.iter().all(|p| p.is_transpose_closed());
+        if transposed {
             // The 'ol switcheroo
             std::mem::swap(&mut width, &mut height);
         }
@@ -40,14 +105,17 @@ impl<'a> Tree<'a> {
             grid[row][0] = Space::Occupied;
             grid[row][width - 1] = Space::Occupied;
         }
+        for &(x, y) in &spec.blocked {
+            if transposed {
+                grid[x + 1][y + 1] = Space::Occupied;
+            } else {
+                grid[y + 1][x + 1] = Space::Occupied;
+            }
+        }
 
-        let demand: Vec<usize> = demand_string
-            .trim()
-            .split(' ')
-            .map(|s| s.parse::<usize>().unwrap())
-            .collect();
+        let demand = spec.demand.clone();
 
-        let mut free_space_remaining = (height - 2) * (width - 2);
+        let mut free_space_remaining = spec.free_cells();
         for (present_idx, present) in present_types.iter().enumerate() {
             free_space_remaining =
                 free_space_remaining.saturating_sub(present.get_size() * demand[present_idx]);
@@ -59,68 +127,72 @@ impl<'a> Tree<'a> {
             demand,
             space_slack: free_space_remaining as isize,
             state: vec![],
+            transposed,
+            pinned: vec![],
+            trail: vec![],
+            steps: vec![],
+            undone: vec![],
         }
     }
 
-    pub fn clone_from_self(&self) -> Self {
-        Self {
-            grid: self.grid.clone(),
-            present_types: self.present_types,
-            demand: self.demand.clone(),
-            state: self.state.clone(),
-            space_slack: self.space_slack,
-        }
-    }
-
+    // Whether the demand covers no more cells than are open. Filling them
+    // exactly counts, so a region this turns down really can't be packed.
     pub fn simple_check(&self) -> bool {
-        let mut grid_space = (self.grid.len() - 2) * (self.grid[0].len() - 2);
+        let mut needed = 0;
         for (poss_idx, possibility) in self.present_types.iter().enumerate() {
-            grid_space = grid_space.saturating_sub(possibility.get_size() * self.demand[poss_idx]);
+            needed += possibility.get_size() * self.demand[poss_idx];
         }
-        grid_space != 0
+        needed <= self.open_cell_count()
+    }
+
+    // Interior cells a present could still cover, so neither blocked,
+    // occupied nor in a pocket
+    pub fn open_cell_count(&self) -> usize {
+        self.grid
+            .iter()
+            .flatten()
+            .filter(|&&space| space == Space::Free)
+            .count()
     }
 
     pub fn try_to_fit(&self) -> bool {
-        if self.space_slack < 0 {
-            return false;
-        }
+        Search::new(self).run(&Cancel::never()).unwrap()
+    }
 
-        if self.demand.iter().all(|&x| x == 0) {
-            return true;
-        }
+    pub fn try_to_fit_before(&self, deadline: Instant) -> Option<bool> {
+        self.try_to_fit_cancellable(&Cancel::at(Some(deadline))).0
+    }
 
-        // Build vector of possibilities
-        let mut possible_move_vector: Vec<PossibleMove> = Vec::new();
-        for (present_idx, present) in self.present_types.iter().enumerate() {
-            if self.demand[present_idx] == 0 {
-                continue;
-            }
-            for possibility_index in 0..self.present_types[present_idx].possibilities.len() {
-                possible_move_vector.push((present_idx, possibility_index));
-            }
-        }
+    // Gives up with None once `cancel` fires, and also says how the search
+    // went
+    pub fn try_to_fit_cancellable(&self, cancel: &Cancel) -> (Option<bool>, Progress) {
+        let mut search = Search::new(self);
+        let verdict = search.run(cancel);
+        (verdict, search.progress())
+    }
 
-        // Build vector of trees
-        let mut possible_trees: Vec<Tree> = Vec::new();
-        for row in 2..(self.grid.len() - 2) {
-            for col in 2..(self.grid[0].len() - 2) {
-                for possible_move in &possible_move_vector {
-                    let mut possible_tree = self.clone_from_self();
-                    if possible_tree.place_present(possible_move.0, possible_move.1, col, row) {
-                        possible_trees.push(possible_tree);
-                    }
-                }
-            }
-        }
+    pub fn try_to_fit_unrestricted(&self) -> bool {
+        Search::unrestricted(self).run(&Cancel::never()).unwrap()
+    }
 
-        for tree in possible_trees {
-            if tree.try_to_fit() {
-                return true;
-            }
-        }
-        false
+    // Cells the remaining demand needs, for the area check
+    pub fn demanded_area(&self) -> usize {
+        self.present_types
+            .iter()
+            .zip(&self.demand)
+            .map(|(present, count)| present.get_size() * count)
+            .sum()
     }
 
+    // Every placement there is on a grid this size, shared with the other
+    // regions of the same size
+    pub fn placement_table(&self) -> Arc<PlacementTable> {
+        self.present_types
+            .placement_table(self.grid.len(), self.grid[0].len())
+    }
+
+    // Changes nothing and returns false if the present collides with
+    // anything, pockets included
     pub fn place_present(
         &mut self,
         present_idx: usize,
@@ -131,87 +203,52 @@ impl<'a> Tree<'a> {
         let pres_ref = &self.present_types[present_idx].possibilities[poss_idx];
 
         // Starting at the top left
-        let pos_x = pos_x - 1;
-        let pos_y = pos_y - 1;
-
-        let mut pocket_vec: Vec<(usize, usize)> = Vec::new();
+        let left = pos_x - 1;
+        let top = pos_y - 1;
 
+        let mut covered: Vec<(usize, usize)> = Vec::new();
         for (row_idx, row) in pres_ref.spaces.iter().enumerate() {
             for (space_idx, pres_space) in row.iter().enumerate() {
-                let x = pos_x + space_idx;
-                let y = pos_y + row_idx;
-
-                let current_space = &mut self.grid[y][x];
-
                 if *pres_space == Space::Occupied {
-                    if *current_space == Space::Occupied {
-                        // COLLISION
-                        return false;
-                    } else {
-                        // NEW OCCUPATION
-                        *current_space = Space::Occupied;
-                    }
-                } else if *current_space != Space::Occupied {
-                    // IT HAS POCKETSSS
-                    *current_space = Space::Pocket;
-                    pocket_vec.push((x, y));
+                    covered.push((left + space_idx, top + row_idx));
                 }
             }
         }
 
-        let mut known_complete_pocket_coords: HashSet<(usize, usize)> = HashSet::new();
-        while let Some(pocket_coord) = pocket_vec.pop() {
-            if known_complete_pocket_coords.contains(&pocket_coord) {
-                continue;
-            }
-            if let Some(pocket_coords_hash) = self.explore_pocket(pocket_coord.0, pocket_coord.1) {
-                known_complete_pocket_coords.extend(pocket_coords_hash);
-            }
+        self.occupy(
+            Placement {
+                present_idx,
+                poss_idx,
+                pos_x,
+                pos_y,
+            },
+            &covered,
+        )
+    }
+
+    // `place_present` for when the covered cells are already worked out
+    pub(super) fn occupy(&mut self, placement: Placement, covered: &[(usize, usize)]) -> bool {
+        if covered.iter().any(|&(x, y)| self.grid[y][x] != Space::Free) {
+            // COLLISION
+            return false;
         }
 
-        for coord in &known_complete_pocket_coords {
-            self.grid[coord.1][coord.0] = Space::Occupied;
+        let step = self.begin_step();
+        let smallest = self.smallest_demanded();
+        for &(x, y) in covered {
+            self.set_space(x, y, Space::Occupied);
         }
 
-        self.space_slack -= known_complete_pocket_coords.len() as isize;
+        self.demand[placement.present_idx] -= 1;
+        if self.smallest_demanded() == smallest {
+            self.mark_pockets(Some(covered));
+        } else {
+            self.mark_pockets(None);
+        }
 
-        self.demand[present_idx] -= 1;
+        self.end_step(step, Some(placement));
         true
     }
-
-    fn explore_pocket(&self, x: usize, y: usize) -> Option<HashSet<(usize, usize)>> {
-        let mut hash_set: HashSet<(usize, usize)> = HashSet::new();
-        let mut to_examine: HashSet<(usize, usize)> = HashSet::new();
-        let mut exploration_queue: VecDeque<(usize, usize)> = VecDeque::new();
-
-        to_examine.insert((x, y));
-        exploration_queue.push_front((x, y));
-
-        while let Some(coord) = exploration_queue.pop_front() {
-            match self.grid[coord.1][coord.0] {
-                Space::Free => {
-                    return None;
-                }
-                Space::Pocket => {
-                    hash_set.insert(coord);
-                    if to_examine.insert((coord.0 - 1, coord.1)) {
-                        exploration_queue.push_back((coord.0 - 1, coord.1));
-                    }
-                    if to_examine.insert((coord.0 + 1, coord.1)) {
-                        exploration_queue.push_back((coord.0 + 1, coord.1));
-                    }
-                    if to_examine.insert((coord.0, coord.1 - 1)) {
-                        exploration_queue.push_back((coord.0, coord.1 - 1));
-                    }
-                    if to_examine.insert((coord.0, coord.1 + 1)) {
-                        exploration_queue.push_back((coord.0, coord.1 + 1));
-                    }
-                }
-                Space::Occupied => {}
-            }
-        }
-        Some(hash_set)
-    }
 }
 
 #[cfg(test)]
@@ -236,6 +273,68 @@ mod tests {
         assert_eq!(tree.space_slack, 158); // (21*11) - (8*1 + 5*5 + 4*10)
     }
 
+    #[test]
+    fn owns_shapes() {
+        let tree = {
+            let poss_vec = vec![PresentPossibilities::new("###\n#..\n###")];
+            Tree::new("4x4: 2", &poss_vec)
+        };
+        let shared = Tree::new("4x4: 3", &tree.present_types);
+
+        assert!(shared.present_types.shares(&tree.present_types));
+        assert!(
+            std::thread::spawn(move || tree.try_to_fit())
+                .join()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_blocked() {
+        let poss = PresentPossibilities::new("###\n###\n###");
+        let poss_vec = vec![poss];
+        let tree = Tree::new("3x5: 1\n...\n.#.\n...\n...\n..#", &poss_vec);
+
+        // Taller than wide, so the grid is stored transposed
+        assert_eq!(tree.grid.len(), 5);
+        assert_eq!(tree.grid[0].len(), 7);
+        assert_eq!(tree.grid[2][2], Space::Occupied);
+        assert_eq!(tree.grid[3][5], Space::Occupied);
+        assert_eq!(tree.open_cell_count(), 13);
+        assert_eq!(tree.space_slack, 4);
+        assert!(tree.simple_check());
+        assert!(!Tree::new("3x3: 1 | blocked 1,1", &poss_vec).simple_check());
+        assert!(Tree::new("3x3: 1", &poss_vec).simple_check());
+    }
+
+    #[test]
+    fn parse_fixed_orientation() {
+        let poss = PresentPossibilities::with_policy("###\n#..\n...", OrientationPolicy::Fixed);
+        let poss_vec = vec![poss];
+        let tree = Tree::new("3x5: 1 | blocked 2,4", &poss_vec);
+
+        // "This side up" means the region can't be turned on its side either
+        assert!(!tree.transposed);
+        assert_eq!(tree.grid.len(), 7);
+        assert_eq!(tree.grid[5][3], Space::Occupied);
+    }
+
+    #[test]
+    fn deadline() {
+        let poss_vec = vec![PresentPossibilities::new("###\n#..\n#..")];
+        let tree = Tree::new("6x6: 3", &poss_vec);
+
+        assert_eq!(tree.try_to_fit_before(Instant::now()), None);
+        assert_eq!(
+            tree.try_to_fit_before(Instant::now() + std::time::Duration::from_secs(60)),
+            Some(tree.try_to_fit())
+        );
+        let (verdict, progress) = tree.try_to_fit_cancellable(&Cancel::never());
+        assert_eq!(verdict, Some(tree.try_to_fit()));
+        assert!(progress.nodes > 1);
+        assert_eq!(tree.demanded_area(), 15);
+    }
+
     #[test]
     fn place_presents() {
         let poss = PresentPossibilities::new(".##\n###\n###"); // idx: 0, space: 8
@@ -0,0 +1,169 @@
+use std::fmt;
+
+use super::*;
+
+// Why a packing a solver claims doesn't hold up
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CertificateError {
+    UnknownPresent(Placement),
+    UnknownOrientation(Placement),
+    OutOfBounds(Placement),
+    Blocked(Placement),
+    Overlap(Placement),
+    WrongCount {
+        present_idx: usize,
+        placed: usize,
+        demanded: usize,
+    },
+}
+
+impl Tree {
+    // Checks that the pins and `placements` pack exactly the demand of
+    // `spec`, which this tree was built from. Only the spec and the shapes
+    // are consulted, not the grid, so a slip in the search's bookkeeping,
+    // pockets included, can't vouch for itself.
+    pub fn check_certificate(
+        &self,
+        spec: &RegionSpec,
+        placements: &[Placement],
+    ) -> Result<(), CertificateError> {
+        let mut taken: HashSet<(usize, usize)> = spec.blocked.iter().copied().collect();
+        let mut placed = vec![0; self.present_types.len()];
+
+        for &placement in self.pinned.iter().chain(placements) {
+            let present = (self.present_types.get(placement.present_idx))
+                .ok_or(CertificateError::UnknownPresent(placement))?;
+            let possibility = (present.possibilities.get(placement.poss_idx))
+                .ok_or(CertificateError::UnknownOrientation(placement))?;
+
+            for (row_idx, row) in possibility.spaces.iter().enumerate() {
+                for (col_idx, space) in row.iter().enumerate() {
+                    if *space != Space::Occupied {
+                        continue;
+                    }
+                    let (row, col) = (placement.pos_y + row_idx)
+                        .checked_sub(2)
+                        .zip((placement.pos_x + col_idx).checked_sub(2))
+                        .ok_or(CertificateError::OutOfBounds(placement))?;
+                    let (x, y) = if self.transposed {
+                        (row, col)
+                    } else {
+                        (col, row)
+                    };
+                    if x >= spec.width || y >= spec.height {
+                        return Err(CertificateError::OutOfBounds(placement));
+                    }
+                    if spec.blocked.contains(&(x, y)) {
+                        return Err(CertificateError::Blocked(placement));
+                    }
+                    if !taken.insert((x, y)) {
+                        return Err(CertificateError::Overlap(placement));
+                    }
+                }
+            }
+            placed[placement.present_idx] += 1;
+        }
+
+        for (present_idx, (&placed, &demanded)) in placed.iter().zip(&spec.demand).enumerate() {
+            if placed != demanded {
+                return Err(CertificateError::WrongCount {
+                    present_idx,
+                    placed,
+                    demanded,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+mod formatting {
+    use super::*;
+
+    impl fmt::Display for CertificateError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let (placement, reason) = match self {
+                Self::UnknownPresent(placement) => (placement, "there is no such present"),
+                Self::UnknownOrientation(placement) => {
+                    (placement, "the present has no such orientation")
+                }
+                Self::OutOfBounds(placement) => (placement, "it sticks out of the region"),
+                Self::Blocked(placement) => (placement, "it covers a blocked cell"),
+                Self::Overlap(placement) => (placement, "it overlaps an earlier present"),
+                Self::WrongCount {
+                    present_idx,
+                    placed,
+                    demanded,
+                } => {
+                    return write!(
+                        f,
+                        "Packing has {placed} of present {present_idx}, but {demanded} are demanded"
+                    );
+                }
+            };
+            write!(
+                f,
+                "Bad placement of present {} orientation {} at {},{}: {}",
+                placement.present_idx, placement.poss_idx, placement.pos_x, placement.pos_y, reason
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packings_check_out() {
+        let poss_vec = vec![PresentPossibilities::new("###\n#..\n###")];
+        for input in ["4x4: 2", "3x6: 2", "5x4: 1 | pinned 0:0@0,0"] {
+            let spec = RegionSpec::new(input);
+            let tree = Tree::from_spec(&spec, &poss_vec);
+            let packing = tree.pack().unwrap();
+            assert_eq!(tree.check_certificate(&spec, &packing.placements), Ok(()));
+        }
+    }
+
+    #[test]
+    fn forgeries() {
+        let poss_vec = vec![PresentPossibilities::new("###\n#..\n###")];
+        let spec = RegionSpec::new("4x4: 2 | blocked 3,3");
+        let tree = Tree::from_spec(&spec, &poss_vec);
+        let at = |poss_idx, pos_x, pos_y| Placement {
+            present_idx: 0,
+            poss_idx,
+            pos_x,
+            pos_y,
+        };
+
+        assert_eq!(
+            tree.check_certificate(&spec, &[at(0, 2, 2)]),
+            Err(CertificateError::WrongCount {
+                present_idx: 0,
+                placed: 1,
+                demanded: 2
+            })
+        );
+        assert_eq!(
+            tree.check_certificate(&spec, &[at(0, 2, 2), at(0, 2, 2)]),
+            Err(CertificateError::Overlap(at(0, 2, 2)))
+        );
+        assert_eq!(
+            tree.check_certificate(&spec, &[at(0, 2, 2), at(0, 3, 3)]),
+            Err(CertificateError::Overlap(at(0, 3, 3)))
+        );
+        assert_eq!(
+            tree.check_certificate(&spec, &[at(0, 3, 3)]),
+            Err(CertificateError::Blocked(at(0, 3, 3)))
+        );
+        assert_eq!(
+            tree.check_certificate(&spec, &[at(0, 5, 2)]),
+            Err(CertificateError::OutOfBounds(at(0, 5, 2)))
+        );
+        assert_eq!(
+            tree.check_certificate(&spec, &[at(9, 2, 2)]),
+            Err(CertificateError::UnknownOrientation(at(9, 2, 2)))
+        );
+    }
+}
@@ -0,0 +1,304 @@
+use super::*;
+use crate::solver::{PROGRESS_EVERY, Progress, Prunes};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Packing {
+    pub placements: Vec<Placement>,
+    pub counts: Vec<usize>,
+    pub value: u64,
+}
+
+// What a search has spent so far, and whether it was told to stop
+struct Effort<'c> {
+    cancel: &'c Cancel<'c>,
+    nodes: u64,
+    pruned: Prunes,
+    started: Instant,
+    cancelled: bool,
+}
+
+impl<'c> Effort<'c> {
+    fn new(cancel: &'c Cancel<'c>) -> Self {
+        Self {
+            cancel,
+            nodes: 0,
+            pruned: Prunes::default(),
+            started: Instant::now(),
+            cancelled: false,
+        }
+    }
+
+    fn progress(&self, depth: usize) -> Progress {
+        Progress::new(self.nodes, depth, self.pruned, (self.started, 0))
+    }
+}
+
+impl Tree {
+    // Largest number of the demanded presents that can be packed at once
+    pub fn max_fit(&self) -> Packing {
+        self.max_fit_weighted(&vec![1; self.present_types.len()])
+    }
+
+    // Same as `max_fit`, but maximises the summed weight of the packed
+    // presents. Sums that don't fit a u64 stop at u64::MAX.
+    pub fn max_fit_weighted(&self, weights: &[u64]) -> Packing {
+        self.max_fit_effort(weights, &mut Effort::new(&Cancel::never()))
+    }
+
+    // A packing of the whole demand, if there is one
+    pub fn pack(&self) -> Option<Packing> {
+        self.pack_cancellable(&Cancel::never()).0.unwrap()
+    }
+
+    // Same as `pack`, but None if `cancel` fired before the search could
+    // tell, and also saying how the search went
+    pub fn pack_cancellable(&self, cancel: &Cancel) -> (Option<Option<Packing>>, Progress) {
+        let mut effort = Effort::new(cancel);
+        let packing = self.max_fit_effort(&vec![1; self.present_types.len()], &mut effort);
+        let verdict = if packing.counts == self.demand {
+            Some(Some(packing))
+        } else if effort.cancelled {
+            None
+        } else {
+            Some(None)
+        };
+        (verdict, effort.progress(0))
+    }
+
+    fn max_fit_effort(&self, weights: &[u64], effort: &mut Effort) -> Packing {
+        assert_eq!(weights.len(), self.present_types.len());
+
+        let mut best = Packing {
+            placements: vec![],
+            counts: vec![0; self.present_types.len()],
+            value: 0,
+        };
+        let ceiling = (0..self.present_types.len())
+            .map(|idx| weights[idx].saturating_mul(self.demand[idx] as u64))
+            .fold(0, u64::saturating_add);
+        // Starting afresh, so the tree's placements are the ones the search made
+        let mut tree = self.clone();
+        tree.settle();
+        tree.branch_and_bound(
+            &self.placement_table(),
+            weights,
+            ceiling,
+            0,
+            &mut best,
+            effort,
+        );
+        best
+    }
+
+    // Returns true once `ceiling` is reached and the search can stop. Works
+    // in place, and leaves the tree as it found it.
+    fn branch_and_bound(
+        &mut self,
+        table: &PlacementTable,
+        weights: &[u64],
+        ceiling: u64,
+        value: u64,
+        best: &mut Packing,
+        effort: &mut Effort,
+    ) -> bool {
+        effort.nodes += 1;
+        if effort.nodes.is_multiple_of(PROGRESS_EVERY) {
+            effort.cancel.report(&effort.progress(self.steps.len()));
+        }
+        if value > best.value {
+            best.value = value;
+            best.placements = self.placements();
+            best.counts = vec![0; self.present_types.len()];
+            for placement in &best.placements {
+                best.counts[placement.present_idx] += 1;
+            }
+        }
+        if best.value >= ceiling {
+            return true;
+        }
+        if effort.cancel.is_cancelled() {
+            effort.cancelled = true;
+            return true;
+        }
+
+        let Some((row, col)) = self.first_open_cell() else {
+            return false;
+        };
+
+        if self.value_bound(weights, value) <= best.value {
+            effort.pruned.bound += 1;
+            return false;
+        }
+
+        // Every packing either covers the first open cell with the first
+        // occupied cell of some orientation, or leaves it empty
+        for idx in table.anchored_at(col, row) {
+            let placement = table.placement(idx);
+            let present_idx = placement.present_idx;
+            if self.demand[present_idx] == 0 || weights[present_idx] == 0 {
+                continue;
+            }
+            if self.occupy(placement, table.cells(idx)) {
+                let done = self.branch_and_bound(
+                    table,
+                    weights,
+                    ceiling,
+                    value.saturating_add(weights[present_idx]),
+                    best,
+                    effort,
+                );
+                self.retract();
+                if done {
+                    return true;
+                }
+            } else {
+                effort.pruned.collision += 1;
+            }
+        }
+
+        self.fill_cell(row, col);
+        let done = self.branch_and_bound(table, weights, ceiling, value, best, effort);
+        self.retract();
+        done
+    }
+
+    fn first_open_cell(&self) -> Option<(usize, usize)> {
+        for row in 1..(self.grid.len() - 1) {
+            for col in 1..(self.grid[0].len() - 1) {
+                if self.grid[row][col] == Space::Free {
+                    return Some((row, col));
+                }
+            }
+        }
+        None
+    }
+
+    // Fractional knapsack over the open cells: no packing below this node
+    // can beat it, since every present needs its full area. Worked out in
+    // u128 so large weights can't overflow it.
+    fn value_bound(&self, weights: &[u64], value: u64) -> u64 {
+        let mut by_density: Vec<usize> = (0..self.present_types.len())
+            .filter(|&idx| self.demand[idx] > 0 && weights[idx] > 0)
+            .collect();
+        by_density.sort_by(|&a, &b| {
+            let lhs = weights[a] as u128 * self.present_types[b].get_size() as u128;
+            let rhs = weights[b] as u128 * self.present_types[a].get_size() as u128;
+            rhs.cmp(&lhs)
+        });
+
+        let mut open_cells = self.open_cell_count() as u128;
+        let mut bound = value as u128;
+        for idx in by_density {
+            let size = self.present_types[idx].get_size() as u128;
+            let weight = weights[idx] as u128;
+            let whole = (open_cells / size).min(self.demand[idx] as u128);
+            bound = bound.saturating_add(whole * weight);
+            open_cells -= whole * size;
+            if whole < self.demand[idx] as u128 {
+                bound = bound.saturating_add(weight * open_cells / size);
+                break;
+            }
+        }
+        u64::try_from(bound).unwrap_or(u64::MAX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn everything_fits() {
+        let poss_vec = vec![PresentPossibilities::new("###\n#..\n###")];
+        let tree = Tree::new("4x4: 2", &poss_vec);
+
+        let packing = tree.max_fit();
+
+        assert_eq!(packing.value, 2);
+        assert_eq!(packing.counts, vec![2]);
+        assert_eq!(packing.placements.len(), 2);
+    }
+
+    #[test]
+    fn partial_fit() {
+        let poss_vec = vec![PresentPossibilities::new("###\n#.#\n###")];
+        let tree = Tree::new("3x6: 3", &poss_vec);
+
+        let packing = tree.max_fit();
+
+        assert_eq!(packing.value, 2);
+        assert_eq!(packing.counts, vec![2]);
+    }
+
+    #[test]
+    fn weighted() {
+        let poss_vec = vec![
+            PresentPossibilities::new("###\n###\n###"),
+            PresentPossibilities::new("#..\n...\n..."),
+        ];
+        let tree = Tree::new("3x3: 1 5", &poss_vec);
+
+        assert_eq!(tree.max_fit().counts, vec![0, 5]);
+
+        let packing = tree.max_fit_weighted(&[10, 1]);
+        assert_eq!(packing.value, 10);
+        assert_eq!(packing.counts, vec![1, 0]);
+    }
+
+    #[test]
+    fn huge_weights() {
+        let poss_vec = vec![
+            PresentPossibilities::new("###\n###\n###"),
+            PresentPossibilities::new("#..\n...\n..."),
+        ];
+        let half = u64::MAX / 2;
+
+        let packing = Tree::new("3x3: 1 1", &poss_vec).max_fit_weighted(&[half, half]);
+        assert_eq!(packing.value, half);
+        assert_eq!(packing.counts.iter().sum::<usize>(), 1);
+
+        // Any three are worth more than a u64 holds, so the search stops there
+        let packing = Tree::new("3x4: 1 3", &poss_vec).max_fit_weighted(&[half, half]);
+        assert_eq!(packing.value, u64::MAX);
+        assert_eq!(packing.counts.iter().sum::<usize>(), 3);
+    }
+
+    #[test]
+    fn placements_replay() {
+        let poss_vec = vec![
+            PresentPossibilities::new("###\n##.\n##."),
+            PresentPossibilities::new("###\n.#.\n###"),
+        ];
+        let tree = Tree::new("6x4: 2 2", &poss_vec);
+
+        let packing = tree.max_fit();
+
+        let mut replayed = tree.clone();
+        for placement in &packing.placements {
+            assert!(replayed.place_present(
+                placement.present_idx,
+                placement.poss_idx,
+                placement.pos_x,
+                placement.pos_y
+            ));
+        }
+        assert_eq!(packing.value, packing.placements.len() as u64);
+    }
+
+    #[test]
+    fn pack() {
+        let poss_vec = vec![PresentPossibilities::new("###\n#.#\n###")];
+
+        assert!(Tree::new("3x6: 2", &poss_vec).pack().is_some());
+        assert!(Tree::new("3x6: 3", &poss_vec).pack().is_none());
+
+        let (packing, progress) = Tree::new("3x6: 2", &poss_vec).pack_cancellable(&Cancel::never());
+        assert_eq!(packing.unwrap().unwrap().counts, vec![2]);
+        assert!(progress.nodes >= 3);
+
+        let (packing, _) = Tree::new("6x6: 4", &poss_vec)
+            .pack_cancellable(&Cancel::at(Some(std::time::Instant::now())));
+        assert_eq!(packing, None);
+    }
+}
@@ -0,0 +1,284 @@
+use std::fmt;
+
+use super::*;
+use crate::region::Pin;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PinError {
+    UnknownPresent(Pin),
+    UnknownOrientation(Pin),
+    OutOfBounds(Pin),
+    ExceedsDemand(Pin),
+    Collision(Pin),
+}
+
+impl Tree {
+    pub fn try_from_spec(
+        spec: &RegionSpec,
+        present_types: impl Into<ShapeSet>,
+    ) -> Result<Self, PinError> {
+        let mut tree = Self::unpinned(spec, present_types.into());
+        for pin in &spec.pinned {
+            tree.pin(pin, spec)?;
+        }
+        tree.mark_pockets(None);
+        tree.settle();
+        Ok(tree)
+    }
+
+    pub fn pinned(&self) -> &[Placement] {
+        &self.pinned
+    }
+
+    // Undoes what pinning does to a position, border and transposition both
+    pub fn region_placement(&self, placement: &Placement) -> RegionPlacement {
+        let (poss_idx, col, row) = if self.transposed {
+            let present = &self.present_types[placement.present_idx];
+            // A grid is only transposed when every orientation has a mirror
+            let poss_idx = present.transposed(placement.poss_idx).unwrap();
+            (poss_idx, placement.pos_y, placement.pos_x)
+        } else {
+            (placement.poss_idx, placement.pos_x, placement.pos_y)
+        };
+        RegionPlacement {
+            present_idx: placement.present_idx,
+            poss_idx,
+            x: col as isize - 2,
+            y: row as isize - 2,
+        }
+    }
+
+    fn pin(&mut self, pin: &Pin, spec: &RegionSpec) -> Result<(), PinError> {
+        let Some(present) = self.present_types.get(pin.present_idx) else {
+            return Err(PinError::UnknownPresent(*pin));
+        };
+        if pin.poss_idx >= present.possibilities.len() {
+            return Err(PinError::UnknownOrientation(*pin));
+        }
+        if !pin.lands_inside(
+            &present.possibilities[pin.poss_idx],
+            spec.width,
+            spec.height,
+        ) {
+            return Err(PinError::OutOfBounds(*pin));
+        }
+        if self.demand[pin.present_idx] == 0 {
+            return Err(PinError::ExceedsDemand(*pin));
+        }
+
+        // Pins are written against the input, so follow the grid if it was
+        // swapped. Some occupied cell is inside, so neither can overflow.
+        let (col, row) = ((pin.x + 2) as usize, (pin.y + 2) as usize);
+        let placement = if self.transposed {
+            Placement {
+                present_idx: pin.present_idx,
+                poss_idx: present
+                    .transposed(pin.poss_idx)
+                    .ok_or(PinError::UnknownOrientation(*pin))?,
+                pos_x: row,
+                pos_y: col,
+            }
+        } else {
+            Placement {
+                present_idx: pin.present_idx,
+                poss_idx: pin.poss_idx,
+                pos_x: col,
+                pos_y: row,
+            }
+        };
+
+        if !self.place_present(
+            placement.present_idx,
+            placement.poss_idx,
+            placement.pos_x,
+            placement.pos_y,
+        ) {
+            return Err(PinError::Collision(*pin));
+        }
+        self.pinned.push(placement);
+        Ok(())
+    }
+}
+
+impl Pin {
+    // Only the occupied cells have to land inside, so a shape with an empty
+    // edge can hang its box over the border, though only by the one cell
+    // the grid's border leaves room for on the left and top
+    pub fn lands_inside(&self, possibility: &Present, width: usize, height: usize) -> bool {
+        if self.x < -1 || self.y < -1 {
+            return false;
+        }
+        for (row_idx, row) in possibility.spaces.iter().enumerate() {
+            for (col_idx, space) in row.iter().enumerate() {
+                if *space != Space::Occupied {
+                    continue;
+                }
+                let inside = (self.x.checked_add_unsigned(col_idx))
+                    .zip(self.y.checked_add_unsigned(row_idx))
+                    .is_some_and(|(x, y)| {
+                        (0..width as isize).contains(&x) && (0..height as isize).contains(&y)
+                    });
+                if !inside {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
+// The pin that puts a reported placement back where it was
+impl From<RegionPlacement> for Pin {
+    fn from(placement: RegionPlacement) -> Self {
+        Self {
+            present_idx: placement.present_idx,
+            poss_idx: placement.poss_idx,
+            x: placement.x,
+            y: placement.y,
+        }
+    }
+}
+
+mod formatting {
+    use super::*;
+
+    impl fmt::Display for PinError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let (pin, reason) = match self {
+                Self::UnknownPresent(pin) => (pin, "there is no such present"),
+                Self::UnknownOrientation(pin) => (pin, "the present has no such orientation"),
+                Self::OutOfBounds(pin) => (pin, "it does not fit inside the region"),
+                Self::ExceedsDemand(pin) => (pin, "more are pinned than demanded"),
+                Self::Collision(pin) => (pin, "it collides with an earlier pin or blocked cell"),
+            };
+            write!(
+                f,
+                "Cannot pin present {} orientation {} at {},{}: {}",
+                pin.present_idx, pin.poss_idx, pin.x, pin.y, reason
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn poss_vec() -> Vec<PresentPossibilities> {
+        vec![
+            PresentPossibilities::new("###\n#..\n###"),
+            PresentPossibilities::new("##.\n...\n..."),
+        ]
+    }
+
+    #[test]
+    fn pinned_demand() {
+        let poss_vec = poss_vec();
+        let tree = Tree::new("6x3: 2 1 | pinned 0:0@0,0", &poss_vec);
+
+        assert_eq!(tree.demand, vec![1, 1]);
+        assert_eq!(tree.pinned().len(), 1);
+        assert_eq!(tree.grid[1][1], Space::Occupied);
+        assert_ne!(tree.grid[2][2], Space::Occupied);
+        assert_eq!(tree.space_slack, 2);
+    }
+
+    #[test]
+    fn pinned_transposed() {
+        let poss_vec = poss_vec();
+        let tree = Tree::new("3x6: 1 1 | pinned 1:0@0,3", &poss_vec);
+
+        // "##." across x=0..1 at y=3 runs down column 4 of the swapped grid
+        assert!(tree.transposed);
+        assert_eq!(tree.grid[1][4], Space::Occupied);
+        assert_eq!(tree.grid[2][4], Space::Occupied);
+        assert_eq!(tree.open_cell_count(), 16);
+    }
+
+    #[test]
+    fn back_to_the_region() {
+        let poss_vec = poss_vec();
+        for input in [
+            "6x3: 2 1 | pinned 0:0@0,0 1:0@3,0",
+            "3x6: 1 1 | pinned 1:0@0,3",
+        ] {
+            let spec = RegionSpec::new(input);
+            let tree = Tree::from_spec(&spec, &poss_vec);
+
+            for (pin, placement) in spec.pinned.iter().zip(tree.pinned()) {
+                let placement = tree.region_placement(placement);
+                assert_eq!(
+                    (placement.present_idx, placement.poss_idx),
+                    (pin.present_idx, pin.poss_idx)
+                );
+                assert_eq!((placement.x, placement.y), (pin.x, pin.y));
+            }
+        }
+    }
+
+    #[test]
+    fn empty_edge_over_the_border() {
+        let poss_vec = poss_vec();
+        // "##." hangs its empty column and rows over the right and bottom,
+        // and turned to stand in the right column, its empty row over the bottom
+        // and over the left and top edges when the first column or row is empty
+        for input in [
+            "5x3: 0 1 | pinned 1:0@3,2",
+            "3x5: 0 1 | pinned 1:1@0,3",
+            "3x3: 0 1 | pinned 1:4@-1,0",
+            "3x3: 0 1 | pinned 1:3@0,-1",
+            "2x4: 0 1 | pinned 1:4@-1,2",
+        ] {
+            let spec = RegionSpec::new(input);
+            let tree = Tree::from_spec(&spec, &poss_vec);
+
+            assert_eq!(tree.check_certificate(&spec, &[]), Ok(()), "{input}");
+            let placement = tree.region_placement(&tree.pinned()[0]);
+            let pin = spec.pinned[0];
+            assert_eq!((placement.x, placement.y), (pin.x, pin.y));
+        }
+
+        for input in ["5x3: 0 1 | pinned 1:0@4,2", "3x3: 0 1 | pinned 1:0@-1,0"] {
+            assert!(matches!(
+                Tree::try_from_spec(&RegionSpec::new(input), &poss_vec),
+                Err(PinError::OutOfBounds(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn collision() {
+        let poss_vec = poss_vec();
+        let spec = RegionSpec::new("6x3: 2 1 | pinned 0:0@0,0 1:0@1,0");
+
+        let err = Tree::try_from_spec(&spec, &poss_vec).unwrap_err();
+
+        assert_eq!(err, PinError::Collision(spec.pinned[1]));
+        assert!(err.to_string().contains("collides"));
+    }
+
+    #[test]
+    fn blocked_collision() {
+        let poss_vec = poss_vec();
+        let spec = RegionSpec::new("6x3: 0 1 | blocked 4,0 | pinned 1:0@3,0");
+
+        assert!(matches!(
+            Tree::try_from_spec(&spec, &poss_vec),
+            Err(PinError::Collision(_))
+        ));
+    }
+
+    #[test]
+    fn rejected() {
+        let poss_vec = poss_vec();
+        for (input, expected) in [
+            ("6x3: 1 1 | pinned 2:0@0,0", "no such present"),
+            ("6x3: 1 1 | pinned 1:9@0,0", "no such orientation"),
+            ("6x3: 1 1 | pinned 0:0@4,0", "does not fit"),
+            ("6x3: 1 0 | pinned 1:0@0,0", "more are pinned"),
+        ] {
+            let err = Tree::try_from_spec(&RegionSpec::new(input), &poss_vec).unwrap_err();
+            assert!(err.to_string().contains(expected), "{input}: {err}");
+        }
+    }
+}
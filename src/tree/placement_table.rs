@@ -0,0 +1,178 @@
+use super::*;
+
+// Every placement that keeps a present's cells inside the interior of a grid
+// of one size, with the cells each one covers, so searches don't have to
+// work them out again at every node. It only depends on the grid size and
+// the shapes, so `ShapeSet::placement_table` shares one between all the
+// regions of that size.
+#[derive(Debug)]
+pub struct PlacementTable {
+    rows: usize,
+    cols: usize,
+    // In the order the backtracking search tries them: by position, then
+    // present, then orientation
+    placements: Vec<Placement>,
+    // The 3x3 box of each placement as bits row * 3 + col, like the
+    // binary format writes shapes
+    masks: Vec<u16>,
+    // (x, y) grid cells of placement i, in reading order, are
+    // cells[starts[i]..starts[i + 1]]
+    cells: Vec<(usize, usize)>,
+    starts: Vec<usize>,
+    // Placements covering each grid cell y * cols + x, by present and then
+    // orientation
+    covering: Vec<Vec<usize>>,
+}
+
+impl PlacementTable {
+    // `rows` and `cols` count the border, as in `Tree::grid`
+    pub fn new(shapes: &[PresentPossibilities], rows: usize, cols: usize) -> Self {
+        let mut table = Self {
+            rows,
+            cols,
+            placements: vec![],
+            masks: vec![],
+            cells: vec![],
+            starts: vec![0],
+            covering: vec![vec![]; rows * cols],
+        };
+
+        // The box can hang over the border, or past the edge of the grid,
+        // as long as the occupied cells stay inside
+        for pos_y in 1..rows {
+            for pos_x in 1..cols {
+                for (present_idx, present) in shapes.iter().enumerate() {
+                    for (poss_idx, possibility) in present.possibilities.iter().enumerate() {
+                        table.add(
+                            Placement {
+                                present_idx,
+                                poss_idx,
+                                pos_x,
+                                pos_y,
+                            },
+                            possibility,
+                        );
+                    }
+                }
+            }
+        }
+
+        for placements in &mut table.covering {
+            placements.sort_by_key(|&idx| {
+                let placement = &table.placements[idx];
+                (placement.present_idx, placement.poss_idx)
+            });
+        }
+        table
+    }
+
+    fn add(&mut self, placement: Placement, possibility: &Present) {
+        let mut mask = 0;
+        let mut cells: Vec<(usize, usize)> = Vec::new();
+        for (row_idx, row) in possibility.spaces.iter().enumerate() {
+            for (col_idx, space) in row.iter().enumerate() {
+                if *space != Space::Occupied {
+                    continue;
+                }
+                let x = placement.pos_x + col_idx - 1;
+                let y = placement.pos_y + row_idx - 1;
+                if x == 0 || y == 0 || x >= self.cols - 1 || y >= self.rows - 1 {
+                    return;
+                }
+                mask |= 1 << (row_idx * 3 + col_idx);
+                cells.push((x, y));
+            }
+        }
+        if cells.is_empty() {
+            return;
+        }
+
+        let idx = self.placements.len();
+        for &(x, y) in &cells {
+            self.covering[y * self.cols + x].push(idx);
+        }
+        self.placements.push(placement);
+        self.masks.push(mask);
+        self.cells.extend(cells);
+        self.starts.push(self.cells.len());
+    }
+
+    pub fn dimensions(&self) -> (usize, usize) {
+        (self.rows, self.cols)
+    }
+
+    pub fn len(&self) -> usize {
+        self.placements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.placements.is_empty()
+    }
+
+    pub fn placement(&self, idx: usize) -> Placement {
+        self.placements[idx]
+    }
+
+    pub fn mask(&self, idx: usize) -> u16 {
+        self.masks[idx]
+    }
+
+    pub fn cells(&self, idx: usize) -> &[(usize, usize)] {
+        &self.cells[self.starts[idx]..self.starts[idx + 1]]
+    }
+
+    pub fn covering(&self, x: usize, y: usize) -> &[usize] {
+        &self.covering[y * self.cols + x]
+    }
+
+    // Placements whose first cell in reading order is (x, y), which are the
+    // ones that can fill it when every cell before it is taken
+    pub fn anchored_at(&self, x: usize, y: usize) -> impl Iterator<Item = usize> + '_ {
+        self.covering(x, y)
+            .iter()
+            .copied()
+            .filter(move |&idx| self.cells(idx)[0] == (x, y))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placements() {
+        let poss_vec = vec![PresentPossibilities::with_policy(
+            "...\n.##\n...",
+            OrientationPolicy::Fixed,
+        )];
+
+        // A domino in a 3x2 region, whose grid has a border around it
+        let table = PlacementTable::new(&poss_vec, 4, 5);
+
+        assert_eq!(table.len(), 4);
+        assert_eq!(table.cells(0), &[(1, 1), (2, 1)]);
+        assert_eq!(table.mask(0), 0b000_110_000);
+        assert_eq!(table.covering(2, 1).len(), 2);
+        assert_eq!(table.anchored_at(2, 1).count(), 1);
+        assert_eq!(table.anchored_at(3, 2).count(), 0);
+    }
+
+    #[test]
+    fn shared_between_regions() {
+        let shapes = ShapeSet::new(vec![PresentPossibilities::new("###\n#..\n###")]);
+        let first = Tree::new("5x4: 1", &shapes);
+        let second = Tree::new("5x4: 2 | blocked 0,0", &shapes);
+        let other = Tree::new("4x4: 1", &shapes);
+
+        assert!(Arc::ptr_eq(
+            &first.placement_table(),
+            &second.placement_table()
+        ));
+        assert!(!Arc::ptr_eq(
+            &first.placement_table(),
+            &other.placement_table()
+        ));
+        // Four orientations at each of the six positions
+        assert_eq!(first.placement_table().len(), 24);
+    }
+}
@@ -0,0 +1,251 @@
+use super::*;
+
+impl Tree {
+    // A pocket is a connected group of free cells, walled in by occupied
+    // ones, with fewer cells than the smallest present still demanded, so
+    // nothing can ever go there. Its cells become `Space::Pocket` and come
+    // off `space_slack`. Placing presents only shrinks groups and raises the
+    // smallest demanded size, so pockets never turn back into free cells
+    // and the result only depends on what is occupied and what is demanded,
+    // not on the order it got that way.
+    //
+    // `around` limits the check to the groups touching those cells, which is
+    // enough after a placement unless the smallest demanded size went up.
+    pub(super) fn mark_pockets(&mut self, around: Option<&[(usize, usize)]>) {
+        let Some(smallest) = self.smallest_demanded() else {
+            return;
+        };
+
+        let seeds: Vec<(usize, usize)> = match around {
+            Some(cells) => cells
+                .iter()
+                .flat_map(|&(x, y)| [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)])
+                .collect(),
+            None => (1..self.grid.len() - 1)
+                .flat_map(|y| (1..self.grid[0].len() - 1).map(move |x| (x, y)))
+                .collect(),
+        };
+
+        let mut big_enough: HashSet<(usize, usize)> = HashSet::new();
+        for (x, y) in seeds {
+            if self.grid[y][x] != Space::Free || big_enough.contains(&(x, y)) {
+                continue;
+            }
+            let group = self.free_group(x, y, smallest);
+            if group.len() < smallest {
+                for &(x, y) in &group {
+                    self.set_space(x, y, Space::Pocket);
+                }
+                self.space_slack -= group.len() as isize;
+            } else {
+                big_enough.extend(group);
+            }
+        }
+    }
+
+    // Cells in pockets, for checking the slack against
+    pub fn pocket_cell_count(&self) -> usize {
+        self.grid
+            .iter()
+            .flatten()
+            .filter(|&&space| space == Space::Pocket)
+            .count()
+    }
+
+    pub(super) fn smallest_demanded(&self) -> Option<usize> {
+        self.present_types
+            .iter()
+            .zip(&self.demand)
+            .filter(|&(_, &count)| count > 0)
+            .map(|(present, _)| present.get_size())
+            .min()
+    }
+
+    // The free cells connected to (x, y), stopping once there are `limit`
+    fn free_group(&self, x: usize, y: usize, limit: usize) -> Vec<(usize, usize)> {
+        let mut group: Vec<(usize, usize)> = vec![(x, y)];
+        let mut seen: HashSet<(usize, usize)> = HashSet::from([(x, y)]);
+        let mut exploration_queue: VecDeque<(usize, usize)> = VecDeque::from([(x, y)]);
+
+        while let Some((x, y)) = exploration_queue.pop_front() {
+            for next in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+                if self.grid[next.1][next.0] == Space::Free && seen.insert(next) {
+                    if group.len() == limit {
+                        return group;
+                    }
+                    group.push(next);
+                    exploration_queue.push_back(next);
+                }
+            }
+        }
+        group
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    fn ring_and_monomino() -> Vec<PresentPossibilities> {
+        vec![
+            PresentPossibilities::new("###\n#.#\n###"),
+            PresentPossibilities::new("...\n.#.\n..."),
+        ]
+    }
+
+    #[test]
+    fn usable_holes_stay_free() {
+        // The ring's hole takes the monomino, so it isn't a pocket
+        let poss_vec = ring_and_monomino();
+        assert!(Tree::new("3x3: 1 1", &poss_vec).try_to_fit());
+
+        // Nor is the gap a C leaves against the wall, which a domino fills
+        let poss_vec = vec![
+            PresentPossibilities::new("###\n#..\n###"),
+            PresentPossibilities::new("...\n.##\n..."),
+        ];
+        assert!(Tree::new("3x3: 1 1", &poss_vec).try_to_fit());
+    }
+
+    #[test]
+    fn unusable_holes() {
+        let poss_vec = ring_and_monomino();
+        let mut tree = Tree::new("6x3: 2 0", &poss_vec);
+
+        assert!(tree.place_present(0, 0, 2, 2));
+
+        assert_eq!(tree.grid[2][2], Space::Pocket);
+        assert_eq!(tree.pocket_cell_count(), 1);
+        assert_eq!(tree.space_slack, 1);
+        assert!(tree.try_to_fit());
+    }
+
+    #[test]
+    fn pockets_appear_when_demand_runs_out() {
+        let poss_vec = ring_and_monomino();
+        let mut tree = Tree::new("6x3: 2 1", &poss_vec);
+
+        assert!(tree.place_present(0, 0, 2, 2));
+        assert_eq!(tree.grid[2][2], Space::Free);
+
+        // The monomino goes nowhere near the hole, but only rings are left
+        assert!(tree.place_present(1, 0, 5, 2));
+        assert_eq!(tree.grid[2][2], Space::Pocket);
+        assert_eq!(tree.pocket_cell_count(), 1);
+    }
+
+    #[test]
+    fn order_independent() {
+        let poss_vec = vec![
+            PresentPossibilities::new("###\n#..\n###"),
+            PresentPossibilities::new("###\n..#\n###"),
+            PresentPossibilities::new("###\n###\n###"),
+        ];
+        let mut forwards = Tree::new("6x3: 1 1 1", &poss_vec);
+        let mut backwards = forwards.clone();
+
+        assert!(forwards.place_present(0, 0, 2, 2));
+        assert!(forwards.place_present(1, 0, 5, 2));
+        assert!(backwards.place_present(1, 0, 5, 2));
+        assert!(backwards.place_present(0, 0, 2, 2));
+
+        assert_eq!(forwards.pocket_cell_count(), 4);
+        assert_eq!(forwards.grid, backwards.grid);
+        assert_eq!(forwards.space_slack, backwards.space_slack);
+    }
+
+    fn count(tree: &Tree, space: Space) -> usize {
+        tree.grid[1..tree.grid.len() - 1]
+            .iter()
+            .flat_map(|row| &row[1..row.len() - 1])
+            .filter(|&&cell| cell == space)
+            .count()
+    }
+
+    // Placements tried at random, most of which collide, on random regions
+    // and shapes
+    #[test]
+    fn accounting_holds_for_random_placements() {
+        let mut rng = Rng::new(51);
+        let mut successes = 0;
+        for _ in 0..200 {
+            let shapes: Vec<PresentPossibilities> = (0..rng.between(1, 3))
+                .map(|_| {
+                    let mask = rng.between(1, 511);
+                    let shape: Vec<String> = (0..3)
+                        .map(|row| {
+                            (0..3)
+                                .map(|col| {
+                                    if mask >> (row * 3 + col) & 1 == 1 {
+                                        '#'
+                                    } else {
+                                        '.'
+                                    }
+                                })
+                                .collect()
+                        })
+                        .collect();
+                    PresentPossibilities::new(&shape.join("\n"))
+                })
+                .collect();
+            let (width, height) = (rng.between(3, 7), rng.between(3, 7));
+            let demand: Vec<String> = (0..shapes.len())
+                .map(|_| rng.between(0, 3).to_string())
+                .collect();
+            let mut input = format!("{width}x{height}: {}", demand.join(" "));
+            if rng.chance(1, 2) {
+                input += &format!(" | blocked {},{}", rng.below(width), rng.below(height));
+            }
+
+            let mut tree = Tree::new(&input, &shapes);
+            let start = tree.clone();
+            let blocked = count(&tree, Space::Occupied);
+            let start_pockets = tree.pocket_cell_count();
+            let rows = tree.grid.len() - 2;
+            let cols = tree.grid[0].len() - 2;
+            let mut placed_area = 0;
+            for _ in 0..30 {
+                let present_idx = rng.below(shapes.len());
+                if tree.demand[present_idx] == 0 {
+                    continue;
+                }
+                let poss_idx = rng.below(shapes[present_idx].possibilities.len());
+                let (pos_x, pos_y) = (rng.between(1, cols), rng.between(1, rows));
+                if tree.place_present(present_idx, poss_idx, pos_x, pos_y) {
+                    placed_area += shapes[present_idx].get_size();
+                    successes += 1;
+                }
+
+                let pockets = tree.pocket_cell_count();
+                assert_eq!(
+                    count(&tree, Space::Occupied),
+                    blocked + placed_area,
+                    "{input}"
+                );
+                assert_eq!(count(&tree, Space::Pocket), pockets, "{input}");
+                assert_eq!(
+                    tree.space_slack,
+                    start.space_slack - (pockets - start_pockets) as isize,
+                    "{input}"
+                );
+            }
+
+            while tree.undo().is_some() {}
+            assert_eq!(tree.grid, start.grid, "{input}");
+            assert_eq!(tree.demand, start.demand, "{input}");
+        }
+        // Not just collisions
+        assert!(successes > 100);
+    }
+
+    #[test]
+    fn walled_off_at_the_start() {
+        let poss_vec = vec![PresentPossibilities::new("###\n#..\n###")];
+        let tree = Tree::new("5x3: 1 | blocked 1,0 1,1 1,2", &poss_vec);
+
+        assert_eq!(tree.pocket_cell_count(), 3);
+        assert_eq!(tree.open_cell_count(), 9);
+        assert_eq!(tree.space_slack, 2);
+    }
+}
@@ -0,0 +1,74 @@
+use super::*;
+
+const LABELS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+impl Tree {
+    // Draws `spec`, which this tree was built from, the way it was written
+    // in the input, whichever way round the grid is stored. Blocked cells are
+    // '#', cells left empty are '.', and each placement gets its own letter,
+    // pins first. Blocked cells come from the spec because the grid can't
+    // tell them apart from pockets that were filled in.
+    pub fn render(&self, spec: &RegionSpec, placements: &[Placement]) -> String {
+        let mut cells: Vec<Vec<char>> = vec![vec!['.'; spec.width]; spec.height];
+        for &(x, y) in &spec.blocked {
+            cells[y][x] = '#';
+        }
+
+        for (idx, placement) in self.pinned.iter().chain(placements).enumerate() {
+            let label = LABELS[idx % LABELS.len()] as char;
+            let possibility =
+                &self.present_types[placement.present_idx].possibilities[placement.poss_idx];
+            for (row_idx, row) in possibility.spaces.iter().enumerate() {
+                for (col_idx, space) in row.iter().enumerate() {
+                    if *space == Space::Occupied {
+                        let row = placement.pos_y + row_idx - 2;
+                        let col = placement.pos_x + col_idx - 2;
+                        if self.transposed {
+                            cells[col][row] = label;
+                        } else {
+                            cells[row][col] = label;
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut rendered = String::with_capacity(spec.height * (spec.width + 1));
+        for row in cells {
+            rendered.extend(row);
+            rendered.push('\n');
+        }
+        rendered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render() {
+        let poss_vec = vec![PresentPossibilities::new("###\n#..\n###")];
+        let spec = RegionSpec::new("4x4: 2 | blocked 3,3");
+        let tree = Tree::from_spec(&spec, &poss_vec);
+
+        assert_eq!(tree.render(&spec, &[]), "....\n....\n....\n...#\n");
+
+        let spec = RegionSpec::new("4x4: 2");
+        let tree = Tree::from_spec(&spec, &poss_vec);
+        let packing = tree.pack().unwrap();
+        let rendered = tree.render(&spec, &packing.placements);
+        assert_eq!(rendered.matches('A').count(), 7);
+        assert_eq!(rendered.matches('B').count(), 7);
+    }
+
+    #[test]
+    fn render_transposed() {
+        let poss_vec = vec![PresentPossibilities::new("###\n#..\n###")];
+        let spec = RegionSpec::new("3x5: 1 | pinned 0:0@0,0 | blocked 2,4");
+        let tree = Tree::from_spec(&spec, &poss_vec);
+
+        assert!(tree.transposed);
+        assert_eq!(tree.render(&spec, &[]), "AAA\nA..\nAAA\n...\n..#\n");
+    }
+}
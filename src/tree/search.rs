@@ -0,0 +1,410 @@
+use std::{
+    fmt, fs,
+    io::{self, ErrorKind},
+    path::Path,
+    time::Duration,
+};
+
+use super::*;
+use crate::{
+    fnv::fnv1a,
+    solver::{PROGRESS_EVERY, Progress, Prunes},
+};
+
+const HEADER: &str = "# search checkpoint v1";
+
+// The backtracking search behind `try_to_fit`, with its stack kept in the
+// open rather than in recursion, so it can stop after any step, be written
+// down as a `Checkpoint`, and carry on later in another process.
+#[derive(Clone, Debug)]
+pub struct Search {
+    tree: Tree,
+    table: Arc<PlacementTable>,
+    // The only candidates for the first placement when the region has
+    // symmetries to break, otherwise every placement in the table
+    first: Option<Vec<usize>>,
+    // Fingerprint of the region before anything was placed
+    region: u64,
+    // For each placement made so far, and for the node being expanded, the
+    // next candidate to try there. The choice below the top of the stack is
+    // always the candidate just before its cursor.
+    stack: Vec<usize>,
+    nodes: u64,
+    pruned: Prunes,
+    // When this process took the search up, and the nodes it had by then
+    started: (Instant, u64),
+    verdict: Option<bool>,
+}
+
+// Where a search had got to, and enough about the region to refuse to
+// resume it on another one
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Checkpoint {
+    pub shapes: u64,
+    pub region: u64,
+    pub nodes: u64,
+    pub stack: Vec<usize>,
+}
+
+impl Search {
+    pub fn new(tree: &Tree) -> Self {
+        let mut search = Self::prepare(tree, true);
+        search.enter();
+        search
+    }
+
+    // Tries every first placement, for checking the symmetry breaking against
+    pub fn unrestricted(tree: &Tree) -> Self {
+        let mut search = Self::prepare(tree, false);
+        search.enter();
+        search
+    }
+
+    // Replays the placements on the checkpoint's stack, so the search goes
+    // on exactly as it would have, node count included
+    pub fn resume(tree: &Tree, checkpoint: &Checkpoint) -> io::Result<Self> {
+        let mut search = Self::prepare(tree, true);
+        if checkpoint.shapes != search.tree.present_types.fingerprint()
+            || checkpoint.region != search.region
+        {
+            return Err(invalid("Search checkpoint is for another region"));
+        }
+        let Some((&top, path)) = checkpoint.stack.split_last() else {
+            return Err(invalid("Search checkpoint has an empty stack"));
+        };
+
+        for (depth, &cursor) in path.iter().enumerate() {
+            let idx = (cursor.checked_sub(1))
+                .filter(|&choice| choice < search.candidate_count(depth))
+                .map(|choice| search.candidate(depth, choice))
+                .ok_or_else(|| invalid("Search checkpoint stack is out of range"))?;
+            let placement = search.table.placement(idx);
+            if search.tree.demand[placement.present_idx] == 0
+                || !search.tree.occupy(placement, search.table.cells(idx))
+            {
+                return Err(invalid("Search checkpoint stack doesn't fit the region"));
+            }
+        }
+        if top > search.candidate_count(path.len()) {
+            return Err(invalid("Search checkpoint stack is out of range"));
+        }
+
+        search.stack = checkpoint.stack.clone();
+        search.nodes = checkpoint.nodes;
+        search.started.1 = checkpoint.nodes;
+        Ok(search)
+    }
+
+    fn prepare(tree: &Tree, break_symmetry: bool) -> Self {
+        let mut tree = tree.clone();
+        tree.settle();
+        let table = tree.placement_table();
+        let first = if break_symmetry {
+            tree.symmetry_broken_moves(&table)
+        } else {
+            None
+        };
+        Self {
+            region: region_fingerprint(&tree),
+            tree,
+            table,
+            first,
+            stack: vec![],
+            nodes: 0,
+            pruned: Prunes::default(),
+            started: (Instant::now(), 0),
+            verdict: None,
+        }
+    }
+
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    pub fn progress(&self) -> Progress {
+        Progress::new(self.nodes, self.tree.steps.len(), self.pruned, self.started)
+    }
+
+    // None until the search has finished
+    pub fn verdict(&self) -> Option<bool> {
+        self.verdict
+    }
+
+    // Holds the packing once the verdict is that it fits
+    pub fn tree(&self) -> &Tree {
+        &self.tree
+    }
+
+    // Gives up with None once `cancel` fires, which is checked once per step,
+    // and can be run again afterwards to carry on
+    pub fn run(&mut self, cancel: &Cancel) -> Option<bool> {
+        let mut report_at = self.nodes + PROGRESS_EVERY;
+        while self.verdict.is_none() {
+            if cancel.is_cancelled() {
+                return None;
+            }
+            self.step();
+            if self.nodes >= report_at {
+                cancel.report(&self.progress());
+                report_at = self.nodes + PROGRESS_EVERY;
+            }
+        }
+        self.verdict
+    }
+
+    // `run`, writing a checkpoint to `path` every so often and when
+    // cancelled. The file goes once the search finishes.
+    pub fn run_checkpointed(
+        &mut self,
+        cancel: &Cancel,
+        path: &Path,
+        every: Duration,
+    ) -> io::Result<Option<bool>> {
+        let mut due = Instant::now() + every;
+        let mut report_at = self.nodes + PROGRESS_EVERY;
+        while self.verdict.is_none() {
+            if cancel.is_cancelled() {
+                self.checkpoint().save(path)?;
+                return Ok(None);
+            }
+            if Instant::now() >= due {
+                self.checkpoint().save(path)?;
+                due = Instant::now() + every;
+            }
+            self.step();
+            if self.nodes >= report_at {
+                cancel.report(&self.progress());
+                report_at = self.nodes + PROGRESS_EVERY;
+            }
+        }
+        match fs::remove_file(path) {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err),
+            _ => Ok(self.verdict),
+        }
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            shapes: self.tree.present_types.fingerprint(),
+            region: self.region,
+            nodes: self.nodes,
+            stack: self.stack.clone(),
+        }
+    }
+
+    // Tries candidates at the top of the stack until one can be placed, or
+    // takes back the placement below once there are none left. Does
+    // nothing once there is a verdict.
+    pub fn step(&mut self) {
+        let depth = match self.verdict {
+            None => self.stack.len() - 1,
+            Some(_) => return,
+        };
+        while self.stack[depth] < self.candidate_count(depth) {
+            let idx = self.candidate(depth, self.stack[depth]);
+            self.stack[depth] += 1;
+            let placement = self.table.placement(idx);
+            if self.tree.demand[placement.present_idx] == 0 {
+                continue;
+            }
+            if self.tree.occupy(placement, self.table.cells(idx)) {
+                self.enter();
+                return;
+            }
+            self.pruned.collision += 1;
+        }
+
+        self.stack.pop();
+        if self.stack.is_empty() {
+            self.verdict = Some(false);
+        } else {
+            self.tree.retract();
+        }
+    }
+
+    // Visits the node the latest placement led to. Placing a present takes
+    // the same from the free cells as from the demand, so only pockets can
+    // leave the slack short below the root.
+    fn enter(&mut self) {
+        self.nodes += 1;
+        if self.tree.space_slack < 0 {
+            if self.stack.is_empty() {
+                self.pruned.slack += 1;
+                self.verdict = Some(false);
+            } else {
+                self.pruned.pocket += 1;
+                self.tree.retract();
+            }
+        } else if self.tree.demand.iter().all(|&count| count == 0) {
+            self.verdict = Some(true);
+        } else {
+            self.stack.push(0);
+        }
+    }
+
+    fn candidate_count(&self, depth: usize) -> usize {
+        match (&self.first, depth) {
+            (Some(first), 0) => first.len(),
+            _ => self.table.len(),
+        }
+    }
+
+    fn candidate(&self, depth: usize, choice: usize) -> usize {
+        match (&self.first, depth) {
+            (Some(first), 0) => first[choice],
+            _ => choice,
+        }
+    }
+}
+
+impl Checkpoint {
+    // None when there is no checkpoint to resume from
+    pub fn load(path: &Path) -> io::Result<Option<Self>> {
+        match fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text).map(Some),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    // Through a temporary file, so a crash halfway leaves the last one whole
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let temporary = path.with_extension("tmp");
+        fs::write(&temporary, self.to_string())?;
+        fs::rename(&temporary, path)
+    }
+
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let mut lines = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+        let mut field = |name: &str| {
+            lines
+                .next()
+                .and_then(|line| line.strip_prefix(name))
+                .and_then(|rest| rest.strip_prefix(' '))
+                .ok_or_else(|| invalid(&format!("Search checkpoint has no {name} line")))
+        };
+        let hex = |value: &str| {
+            u64::from_str_radix(value, 16)
+                .map_err(|_| invalid(&format!("Bad search checkpoint value {value:?}")))
+        };
+        let decimal = |value: &str| {
+            value
+                .parse::<u64>()
+                .map_err(|_| invalid(&format!("Bad search checkpoint value {value:?}")))
+        };
+
+        let shapes = hex(field("shapes")?)?;
+        let region = hex(field("region")?)?;
+        let nodes = decimal(field("nodes")?)?;
+        let stack = field("stack")?
+            .split_whitespace()
+            .map(|value| decimal(value).map(|cursor| cursor as usize))
+            .collect::<io::Result<Vec<usize>>>()?;
+        Ok(Self {
+            shapes,
+            region,
+            nodes,
+            stack,
+        })
+    }
+}
+
+// FNV-1a over the grid and the demand, like `ShapeSet::fingerprint`
+fn region_fingerprint(tree: &Tree) -> u64 {
+    let grid = tree.grid.iter().flatten().map(Space::to_string);
+    let demand = tree.demand.iter().map(|count| format!("{count},"));
+    fnv1a(grid.chain(demand).flat_map(String::into_bytes))
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+mod formatting {
+    use super::*;
+
+    impl fmt::Display for Checkpoint {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            writeln!(f, "{HEADER}")?;
+            writeln!(f, "shapes {:016x}", self.shapes)?;
+            writeln!(f, "region {:016x}", self.region)?;
+            writeln!(f, "nodes {}", self.nodes)?;
+            write!(f, "stack")?;
+            for cursor in &self.stack {
+                write!(f, " {cursor}")?;
+            }
+            writeln!(f)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shapes() -> ShapeSet {
+        ShapeSet::new(vec![PresentPossibilities::new("###\n#..\n#..")])
+    }
+
+    #[test]
+    fn stop_and_resume() {
+        let tree = Tree::new("6x6: 6", shapes());
+        let mut whole = Search::new(&tree);
+        let verdict = whole.run(&Cancel::never());
+
+        let mut search = Search::new(&tree);
+        for _ in 0..50 {
+            search.step();
+        }
+        assert_eq!(search.verdict(), None);
+        let checkpoint = Checkpoint::parse(&search.checkpoint().to_string()).unwrap();
+        assert_eq!(checkpoint, search.checkpoint());
+
+        let mut resumed = Search::resume(&tree, &checkpoint).unwrap();
+        assert_eq!(resumed.run(&Cancel::never()), verdict);
+        assert_eq!(resumed.nodes(), whole.nodes());
+        assert_eq!(
+            resumed.tree().placements().len(),
+            whole.tree().placements().len()
+        );
+    }
+
+    #[test]
+    fn other_regions_refused() {
+        let tree = Tree::new("6x6: 6", shapes());
+        let mut search = Search::new(&tree);
+        search.step();
+        let checkpoint = search.checkpoint();
+
+        assert!(Search::resume(&Tree::new("6x6: 5", shapes()), &checkpoint).is_err());
+        assert!(Search::resume(&Tree::new("6x6: 6 | blocked 5,5", shapes()), &checkpoint).is_err());
+        let garbled = Checkpoint {
+            stack: vec![usize::MAX, 0],
+            ..checkpoint.clone()
+        };
+        assert!(Search::resume(&tree, &garbled).is_err());
+        assert!(Checkpoint::parse("shapes 0\nregion 0\nnodes many\nstack 0").is_err());
+    }
+
+    #[test]
+    fn checkpoint_file() {
+        let path = std::env::temp_dir().join(format!("search-{}.checkpoint", std::process::id()));
+        let tree = Tree::new("6x6: 6", shapes());
+
+        // Cancelled straight away, so all it does is write where it started
+        let mut search = Search::new(&tree);
+        let stopped =
+            search.run_checkpointed(&Cancel::at(Some(Instant::now())), &path, Duration::ZERO);
+        assert_eq!(stopped.unwrap(), None);
+        let checkpoint = Checkpoint::load(&path).unwrap().unwrap();
+
+        let mut resumed = Search::resume(&tree, &checkpoint).unwrap();
+        let finished = resumed.run_checkpointed(&Cancel::never(), &path, Duration::ZERO);
+        assert_eq!(finished.unwrap(), Some(tree.try_to_fit()));
+        assert_eq!(Checkpoint::load(&path).unwrap(), None);
+    }
+}
@@ -0,0 +1,198 @@
+use super::*;
+use crate::presents::Transform;
+
+impl Tree {
+    // Transforms of the square that map the region, blocked cells and
+    // pins onto themselves, and every allowed orientation onto another one
+    pub fn region_symmetries(&self) -> Vec<Transform> {
+        let rows = self.grid.len() - 2;
+        let cols = self.grid[0].len() - 2;
+
+        Transform::all()
+            .into_iter()
+            .filter(|transform| rows == cols || transform.rotations % 2 == 0)
+            .filter(|&transform| {
+                (0..rows).all(|row| {
+                    (0..cols).all(|col| {
+                        let (image_row, image_col) =
+                            transform_cell(transform, row, col, rows, cols);
+                        (self.grid[row + 1][col + 1] == Space::Free)
+                            == (self.grid[image_row + 1][image_col + 1] == Space::Free)
+                    })
+                })
+            })
+            .filter(|&transform| {
+                self.present_types.iter().all(|present| {
+                    present.possibilities.iter().all(|possibility| {
+                        present
+                            .possibilities
+                            .contains(&possibility.transformed(transform))
+                    })
+                })
+            })
+            .collect()
+    }
+
+    // Every packing has a copy that places the least symmetric demanded
+    // present where it is the smallest of its images under the region's
+    // symmetries, so the first placement only has to try those. They come
+    // back as indices into `table`, in its order.
+    pub(super) fn symmetry_broken_moves(&self, table: &PlacementTable) -> Option<Vec<usize>> {
+        let symmetries = self.region_symmetries();
+        if symmetries.len() == 1 {
+            return None;
+        }
+
+        let present_idx = (0..self.present_types.len())
+            .filter(|&idx| self.demand[idx] > 0)
+            .min_by_key(|&idx| self.present_types[idx].symmetry_group().order())?;
+
+        let rows = self.grid.len() - 2;
+        let cols = self.grid[0].len() - 2;
+
+        let moves = (0..table.len())
+            .filter(|&idx| table.placement(idx).present_idx == present_idx)
+            .filter(|&idx| {
+                let cells = self.covered_cells(&table.placement(idx));
+                symmetries.iter().all(|&transform| {
+                    let mut image: Vec<(usize, usize)> = cells
+                        .iter()
+                        .map(|&(row, col)| transform_cell(transform, row, col, rows, cols))
+                        .collect();
+                    image.sort();
+                    cells <= image
+                })
+            })
+            .collect();
+        Some(moves)
+    }
+
+    // Sorted interior (row, col) cells a placement would occupy
+    fn covered_cells(&self, placement: &Placement) -> Vec<(usize, usize)> {
+        let possibility =
+            &self.present_types[placement.present_idx].possibilities[placement.poss_idx];
+        let mut cells: Vec<(usize, usize)> = Vec::new();
+        for (row_idx, row) in possibility.spaces.iter().enumerate() {
+            for (col_idx, space) in row.iter().enumerate() {
+                if *space == Space::Occupied {
+                    cells.push((placement.pos_y + row_idx - 2, placement.pos_x + col_idx - 2));
+                }
+            }
+        }
+        cells.sort();
+        cells
+    }
+}
+
+// Same convention as `Present::transformed`: mirror left-right, then turn
+// clockwise, on a rows x cols grid
+fn transform_cell(
+    transform: Transform,
+    mut row: usize,
+    mut col: usize,
+    mut rows: usize,
+    mut cols: usize,
+) -> (usize, usize) {
+    if transform.flipped {
+        col = cols - 1 - col;
+    }
+    for _ in 0..transform.rotations {
+        (row, col) = (col, rows - 1 - row);
+        std::mem::swap(&mut rows, &mut cols);
+    }
+    (row, col)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn poss_vec() -> Vec<PresentPossibilities> {
+        vec![
+            PresentPossibilities::new("###\n#..\n#.."),
+            PresentPossibilities::new("##.\n#..\n..."),
+            PresentPossibilities::new("###\n#.#\n###"),
+        ]
+    }
+
+    #[test]
+    fn transform_cell_matches_present() {
+        let pres = PresentPossibilities::new("##.\n#..\n...").possibilities[0].clone();
+        for transform in Transform::all() {
+            let image = pres.transformed(transform);
+            for row in 0..3 {
+                for col in 0..3 {
+                    let (image_row, image_col) = transform_cell(transform, row, col, 3, 3);
+                    assert_eq!(pres.spaces[row][col], image.spaces[image_row][image_col]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn region_symmetries() {
+        let poss_vec = poss_vec();
+
+        assert_eq!(
+            Tree::new("4x4: 1 0 0", &poss_vec).region_symmetries().len(),
+            8
+        );
+        assert_eq!(
+            Tree::new("5x4: 1 0 0", &poss_vec).region_symmetries().len(),
+            4
+        );
+        assert_eq!(
+            Tree::new("5x4: 1 0 0 | blocked 0,0", &poss_vec)
+                .region_symmetries()
+                .len(),
+            1
+        );
+        assert_eq!(
+            Tree::new("5x5: 1 0 0 | blocked 0,0 4,4", &poss_vec)
+                .region_symmetries()
+                .len(),
+            4
+        );
+
+        // Turning is fine, but mirroring would need the other hand of the L
+        let rotations = vec![PresentPossibilities::with_policy(
+            "###\n#..\n...",
+            OrientationPolicy::Rotations,
+        )];
+        assert_eq!(Tree::new("4x4: 1", &rotations).region_symmetries().len(), 4);
+    }
+
+    #[test]
+    fn fewer_first_placements() {
+        let poss_vec = poss_vec();
+        let tree = Tree::new("5x5: 1 0 0", &poss_vec);
+
+        // 9 box positions x 8 orientations, most of them the same up to symmetry
+        let table = tree.placement_table();
+        let first_moves = tree.symmetry_broken_moves(&table).unwrap();
+        assert!(first_moves.len() < 72 / 4);
+        assert!(!first_moves.is_empty());
+    }
+
+    #[test]
+    fn verdicts_match_unrestricted() {
+        let poss_vec = poss_vec();
+        for input in [
+            "4x4: 2 0 0",
+            "5x4: 2 0 0",
+            "5x5: 1 1 0",
+            "5x5: 0 0 2",
+            "4x4: 0 0 2",
+            "4x4: 3 0 0",
+            "5x4: 1 4 0",
+            "4x4: 0 3 0",
+            "6x3: 0 0 2",
+            "6x3: 2 0 0",
+            "4x4: 1 1 0 | blocked 0,0 3,3",
+            "4x5: 1 1 0 | blocked 1,2",
+        ] {
+            let tree = Tree::new(input, &poss_vec);
+            assert_eq!(tree.try_to_fit(), tree.try_to_fit_unrestricted(), "{input}");
+        }
+    }
+}
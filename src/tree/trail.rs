@@ -0,0 +1,161 @@
+use super::*;
+
+// One change to the tree that `retract` can take back: cells in the trail
+// from `trail_len` onwards, and the slack from before
+#[derive(Clone, Debug)]
+pub(super) struct Step {
+    // None when a cell was filled in without placing anything
+    placement: Option<Placement>,
+    trail_len: usize,
+    space_slack: isize,
+}
+
+impl Tree {
+    // Takes back the latest placement, restoring the grid, any pockets it
+    // filled in, the slack and the demand exactly. Pins stay put.
+    pub fn undo(&mut self) -> Option<Placement> {
+        let placement = self.steps.last()?.placement?;
+        self.retract();
+        self.undone.push(placement);
+        Some(placement)
+    }
+
+    // Places the latest undone present again, until something else is placed
+    pub fn redo(&mut self) -> Option<Placement> {
+        let placement = self.undone.pop()?;
+        let undone = std::mem::take(&mut self.undone);
+        self.place_present(
+            placement.present_idx,
+            placement.poss_idx,
+            placement.pos_x,
+            placement.pos_y,
+        );
+        self.undone = undone;
+        Some(placement)
+    }
+
+    // Placements made since the tree was built, oldest first, not counting pins
+    pub fn placements(&self) -> Vec<Placement> {
+        self.steps
+            .iter()
+            .filter_map(|step| step.placement)
+            .collect()
+    }
+
+    pub(super) fn begin_step(&self) -> Step {
+        Step {
+            placement: None,
+            trail_len: self.trail.len(),
+            space_slack: self.space_slack,
+        }
+    }
+
+    pub(super) fn end_step(&mut self, step: Step, placement: Option<Placement>) {
+        self.steps.push(Step { placement, ..step });
+        self.undone.clear();
+    }
+
+    // Sets a grid cell, remembering what was there
+    pub(super) fn set_space(&mut self, x: usize, y: usize, space: Space) {
+        self.trail.push((x, y, self.grid[y][x]));
+        self.grid[y][x] = space;
+    }
+
+    // Puts back every cell changed since `step` began
+    pub(super) fn rewind(&mut self, step: &Step) {
+        while self.trail.len() > step.trail_len {
+            let (x, y, space) = self.trail.pop().unwrap();
+            self.grid[y][x] = space;
+        }
+        self.space_slack = step.space_slack;
+    }
+
+    // Undoes the latest step of a search, leaving redo alone
+    pub(super) fn retract(&mut self) {
+        let step = self.steps.pop().unwrap();
+        self.rewind(&step);
+        if let Some(placement) = step.placement {
+            self.demand[placement.present_idx] += 1;
+        }
+    }
+
+    // Marks an open cell as one that will stay empty, as a step of its own
+    pub(super) fn fill_cell(&mut self, row: usize, col: usize) {
+        let step = self.begin_step();
+        self.set_space(col, row, Space::Occupied);
+        self.space_slack -= 1;
+        self.mark_pockets(Some(&[(col, row)]));
+        self.end_step(step, None);
+    }
+
+    // What is placed so far becomes part of the region and can't be undone
+    pub(super) fn settle(&mut self) {
+        self.trail.clear();
+        self.steps.clear();
+        self.undone.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(tree: &Tree) -> (Vec<Vec<Space>>, Vec<usize>, isize) {
+        (tree.grid.clone(), tree.demand.clone(), tree.space_slack)
+    }
+
+    #[test]
+    fn undo_restores() {
+        let poss_vec = vec![
+            PresentPossibilities::new("###\n#.#\n###"),
+            PresentPossibilities::new("###\n#..\n###"),
+        ];
+        let mut tree = Tree::new("6x3: 1 1", &poss_vec);
+        let before = snapshot(&tree);
+
+        // The ring's middle becomes a pocket
+        assert!(tree.place_present(0, 0, 2, 2));
+        let ring = snapshot(&tree);
+        assert_eq!(tree.grid[2][2], Space::Pocket);
+        assert!(tree.place_present(1, 0, 5, 2));
+        assert_eq!(tree.placements().len(), 2);
+
+        assert_eq!(tree.undo().map(|placement| placement.present_idx), Some(1));
+        assert_eq!(snapshot(&tree), ring);
+        assert_eq!(tree.undo().map(|placement| placement.present_idx), Some(0));
+        assert_eq!(snapshot(&tree), before);
+        assert_eq!(tree.undo(), None);
+    }
+
+    #[test]
+    fn collision_changes_nothing() {
+        let poss_vec = vec![PresentPossibilities::new("###\n#..\n###")];
+        let mut tree = Tree::new("4x4: 2", &poss_vec);
+        assert!(tree.place_present(0, 0, 2, 2));
+        let before = snapshot(&tree);
+
+        assert!(!tree.place_present(0, 0, 3, 3));
+
+        assert_eq!(snapshot(&tree), before);
+        assert_eq!(tree.placements().len(), 1);
+    }
+
+    #[test]
+    fn redo() {
+        let poss_vec = vec![PresentPossibilities::new("###\n#..\n###")];
+        let mut tree = Tree::new("4x4: 2 | pinned 0:0@0,0", &poss_vec);
+        assert!(tree.place_present(0, 2, 3, 3));
+        let placed = snapshot(&tree);
+
+        // The pin is part of the region now
+        let placement = tree.undo().unwrap();
+        assert_eq!(tree.undo(), None);
+        assert_eq!(tree.redo(), Some(placement));
+        assert_eq!(snapshot(&tree), placed);
+        assert_eq!(tree.redo(), None);
+
+        tree.undo();
+        assert!(tree.place_present(0, 2, 3, 3));
+        assert_eq!(tree.redo(), None);
+    }
+}